@@ -0,0 +1,189 @@
+//! `--alphabetize-items`: sorts the top-level items of the inlined crate by name, for
+//! stable diffs of committed scripts. Each item keeps a slot of its own kind and
+//! visibility, so the layout stays the same and only the names change order. `use`s,
+//! macros and other unnamed items stay where they are and nothing moves across them,
+//! and an impl directly following its type moves along with it.
+
+use std::collections::{HashMap, VecDeque};
+
+use syn::{Item, Visibility};
+
+use crate::strip_private::impl_type;
+
+/// What an item is sorted among: its kind and visibility
+type Group = (&'static str, String);
+
+/// An item with the impls that follow it; `group` is `None` for items that stay put
+struct Unit {
+    group: Option<Group>,
+    name: String,
+    items: Vec<Item>,
+}
+
+/// Sorts the top-level items of `file`, returning whether any item moved
+pub fn alphabetize(file: &mut syn::File) -> bool {
+    let mut units: Vec<Unit> = Vec::new();
+    for item in std::mem::take(&mut file.items) {
+        let follows_type = matches!(
+            (units.last(), impl_type(&item)),
+            (Some(Unit { group: Some(_), name, .. }), Some(ty)) if *name == ty
+        );
+        if follows_type {
+            units.last_mut().expect("checked above").items.push(item);
+            continue;
+        }
+
+        let (group, name) = match sort_key(&item) {
+            Some((group, name)) => (Some(group), name),
+            None => (None, String::new()),
+        };
+        units.push(Unit {
+            group,
+            name,
+            items: vec![item],
+        });
+    }
+
+    let before: Vec<String> = units.iter().map(|unit| unit.name.clone()).collect();
+    let mut sorted = Vec::with_capacity(units.len());
+    let mut run = Vec::new();
+    for unit in units {
+        if unit.group.is_some() {
+            run.push(unit);
+            continue;
+        }
+        sorted.extend(sort_run(std::mem::take(&mut run)));
+        sorted.push(unit);
+    }
+    sorted.extend(sort_run(run));
+
+    let moved = sorted.iter().map(|unit| &unit.name).ne(before.iter());
+    file.items = sorted.into_iter().flat_map(|unit| unit.items).collect();
+    moved
+}
+
+/// Sorts a run of movable units by name, each within the slots of its group
+fn sort_run(run: Vec<Unit>) -> Vec<Unit> {
+    let slots: Vec<Group> = run
+        .iter()
+        .map(|unit| unit.group.clone().expect("runs hold movable units"))
+        .collect();
+
+    let mut groups: HashMap<Group, Vec<Unit>> = HashMap::new();
+    for unit in run {
+        let group = unit.group.clone().expect("runs hold movable units");
+        groups.entry(group).or_default().push(unit);
+    }
+    let mut groups: HashMap<Group, VecDeque<Unit>> = groups
+        .into_iter()
+        .map(|(group, mut units)| {
+            units.sort_by(|a, b| {
+                a.name
+                    .to_lowercase()
+                    .cmp(&b.name.to_lowercase())
+                    .then_with(|| a.name.cmp(&b.name))
+            });
+            (group, units.into())
+        })
+        .collect();
+
+    slots
+        .iter()
+        .map(|slot| {
+            groups
+                .get_mut(slot)
+                .and_then(VecDeque::pop_front)
+                .expect("one unit per slot")
+        })
+        .collect()
+}
+
+fn sort_key(item: &Item) -> Option<(Group, String)> {
+    let (kind, vis, ident) = match item {
+        Item::Const(item) => ("const", &item.vis, &item.ident),
+        Item::Enum(item) => ("enum", &item.vis, &item.ident),
+        Item::Fn(item) => ("fn", &item.vis, &item.sig.ident),
+        Item::Mod(item) => ("mod", &item.vis, &item.ident),
+        Item::Static(item) => ("static", &item.vis, &item.ident),
+        Item::Struct(item) => ("struct", &item.vis, &item.ident),
+        Item::Trait(item) => ("trait", &item.vis, &item.ident),
+        Item::TraitAlias(item) => ("trait alias", &item.vis, &item.ident),
+        Item::Type(item) => ("type", &item.vis, &item.ident),
+        Item::Union(item) => ("union", &item.vis, &item.ident),
+        _ => return None,
+    };
+    Some(((kind, visibility(vis)), ident.to_string()))
+}
+
+fn visibility(vis: &Visibility) -> String {
+    match vis {
+        Visibility::Public(_) => "pub".to_string(),
+        Visibility::Restricted(restricted) => {
+            let path: Vec<String> = restricted
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect();
+            format!("pub({})", path.join("::"))
+        }
+        Visibility::Inherited => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "
+use std::fmt;
+pub fn zeta() {}
+fn beta() {}
+pub fn Alpha() {}
+struct Yak;
+impl Yak { fn new() -> Self { Yak } }
+fn alpha() {}
+struct Bee;
+macro_rules! m { () => {} }
+fn d() {}
+fn c() {}
+";
+
+    fn names(file: &syn::File) -> Vec<String> {
+        file.items
+            .iter()
+            .map(|item| match sort_key(item) {
+                Some((_, name)) => name,
+                None if impl_type(item).is_some() => "impl".to_string(),
+                None => "-".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sorts_within_kind_and_visibility_slots() {
+        let mut file = syn::parse_file(INPUT).unwrap();
+        assert!(alphabetize(&mut file));
+        assert_eq!(
+            names(&file),
+            [
+                "-", "Alpha", "alpha", "zeta", "Bee", "beta", "Yak", "impl", "-", "c", "d"
+            ]
+        );
+    }
+
+    #[test]
+    fn sorting_is_deterministic() {
+        let mut once = syn::parse_file(INPUT).unwrap();
+        alphabetize(&mut once);
+        let mut twice = once.clone();
+        assert!(!alphabetize(&mut twice));
+        assert_eq!(once, twice);
+
+        for _ in 0..10 {
+            let mut again = syn::parse_file(INPUT).unwrap();
+            alphabetize(&mut again);
+            assert_eq!(again, once);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! `--embed-assets`: replaces `include_str!`/`include_bytes!` calls that name a file
+//! with a string literal with the file's contents, so the script doesn't depend on files
+//! next to it. `include_str!` becomes a `"..."` literal and `include_bytes!` a `b"..."`
+//! literal, which have the same types as the macros.
+
+use std::path::Path;
+
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{Expr, ExprLit, Lit, LitByteStr, LitStr};
+
+/// Size from which an embedded binary file is called out, since every non-printable
+/// byte takes four characters in a byte string literal
+const LARGE_BINARY: usize = 16 * 1024;
+
+/// Embeds the files included by `file`, resolving relative paths against the directory
+/// of `source_path` like rustc does. Returns a warning for each include left in place
+/// and each large binary file embedded.
+pub fn embed_assets(file: &mut syn::File, source_path: &Path) -> Vec<String> {
+    let mut embedder = Embedder {
+        source_path,
+        warnings: Vec::new(),
+    };
+    embedder.visit_file_mut(file);
+    embedder.warnings
+}
+
+struct Embedder<'a> {
+    source_path: &'a Path,
+    warnings: Vec<String>,
+}
+
+impl Embedder<'_> {
+    /// The literal replacing an `include_str!`/`include_bytes!` call, if it can be embedded
+    fn embed(&mut self, mac: &syn::Macro) -> Option<Lit> {
+        let name = mac.path.get_ident()?.to_string();
+        if name != "include_str" && name != "include_bytes" {
+            return None;
+        }
+
+        let start = mac.path.span().start();
+        let location = format!(
+            "`{name}!` at {}:{}:{}",
+            self.source_path.display(),
+            start.line,
+            start.column + 1
+        );
+        let Ok(literal) = mac.parse_body::<LitStr>() else {
+            self.warnings.push(format!(
+                "{location} can't be embedded, its path isn't a plain string literal (e.g. built with `concat!` or `env!`)"
+            ));
+            return None;
+        };
+
+        let path = self
+            .source_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(literal.value());
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => {
+                crate::cache::track(&path);
+                bytes
+            }
+            Err(e) => {
+                self.warnings.push(format!(
+                    "{location} can't be embedded, failed to read '{}': {e}",
+                    path.display()
+                ));
+                return None;
+            }
+        };
+
+        let span = literal.span();
+        if name == "include_bytes" {
+            if bytes.len() >= LARGE_BINARY && std::str::from_utf8(&bytes).is_err() {
+                self.warnings.push(format!(
+                    "{location} embeds binary file '{}' ({} bytes), which grows the output by up to four times that",
+                    path.display(),
+                    bytes.len()
+                ));
+            }
+            return Some(Lit::ByteStr(LitByteStr::new(&bytes, span)));
+        }
+
+        match String::from_utf8(bytes) {
+            Ok(content) => Some(Lit::Str(LitStr::new(&content, span))),
+            Err(_) => {
+                self.warnings.push(format!(
+                    "{location} can't be embedded, '{}' is not valid UTF-8",
+                    path.display()
+                ));
+                None
+            }
+        }
+    }
+}
+
+impl VisitMut for Embedder<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Macro(mac) = expr
+            && let Some(lit) = self.embed(&mac.mac)
+        {
+            *expr = Expr::Lit(ExprLit {
+                attrs: std::mem::take(&mut mac.attrs),
+                lit,
+            });
+            return;
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+}
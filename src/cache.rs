@@ -0,0 +1,163 @@
+//! `--cache`: skips regenerating an `--output` that is already up to date. Next to the
+//! output, a record keeps the modification time of every source file read for it and a key
+//! hashing everything else that decides the output: the options as parsed, the resolved
+//! manifest and the shebang. The output is reused only while the record still matches, so
+//! editing Cargo.toml or changing the shebang invalidates it even when no source file was
+//! touched.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+
+use crate::{Result, ScriptifyError};
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+static SOURCES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+pub fn set_tracking(enabled: bool) {
+    TRACKING.store(enabled, Ordering::Relaxed);
+}
+
+/// Notes that `path` was read to produce the output
+pub fn track(path: &Path) {
+    if TRACKING.load(Ordering::Relaxed) {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        SOURCES.lock().unwrap_or_else(|e| e.into_inner()).push(path);
+    }
+}
+
+/// Hash of what the output depends on besides its source files. `options` is the `Debug`
+/// form of the parsed options, so values read from the environment while parsing (like
+/// `{{env.NAME}}` placeholders) count with their values. `manifest` is the manifest as
+/// embedded (`None` without one).
+pub fn key(options: &str, manifest: Option<&str>, shebang: &str) -> String {
+    let mut hashed = format!("scriptify {}\0", env!("CARGO_PKG_VERSION"));
+    hashed.push_str(options);
+    // `Debug` escapes NUL, so these separators keep the sections apart
+    hashed.push_str("\0\0manifest\0");
+    hashed.push_str(manifest.unwrap_or("(none)"));
+    hashed.push_str("\0\0shebang\0");
+    hashed.push_str(shebang);
+    format!("{:x}", Sha256::digest(hashed.as_bytes()))
+}
+
+/// Whether `output` exists and its record matches `key` and the current source files
+pub fn is_fresh(output: &Path, key: &str) -> bool {
+    let Ok(content) = std::fs::read_to_string(record_path(output)) else {
+        return false;
+    };
+    let Ok(record) = toml::from_str::<toml::Table>(&content) else {
+        return false;
+    };
+    if !output.is_file() || record.get("key").and_then(toml::Value::as_str) != Some(key) {
+        return false;
+    }
+
+    record
+        .get("sources")
+        .and_then(toml::Value::as_table)
+        .is_some_and(|sources| {
+            sources.iter().all(|(path, recorded)| {
+                modified(Path::new(path)).is_some_and(|time| recorded.as_integer() == Some(time))
+            })
+        })
+}
+
+/// Writes the record for `output`, generated under `key` from the tracked source files
+pub fn store(output: &Path, key: &str) -> Result<()> {
+    let mut sources = toml::Table::new();
+    for path in SOURCES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        if let Some(time) = modified(path) {
+            sources.insert(path.display().to_string(), toml::Value::Integer(time));
+        }
+    }
+
+    let mut record = toml::Table::new();
+    record.insert("key".to_string(), toml::Value::String(key.to_string()));
+    record.insert("sources".to_string(), toml::Value::Table(sources));
+    let content = toml::to_string(&record)
+        .map_err(|e| ScriptifyError::Other(format!("failed to write cache record: {e}")))?;
+
+    let path = record_path(output);
+    std::fs::write(&path, content).map_err(ScriptifyError::io(format!(
+        "failed to write cache record '{}'",
+        path.display()
+    )))
+}
+
+/// `<OUTPUT>.scriptify-cache`
+fn record_path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".scriptify-cache");
+    output.with_file_name(name)
+}
+
+/// Modification time in nanoseconds since the epoch
+fn modified(path: &Path) -> Option<i64> {
+    let time = std::fs::metadata(path).ok()?.modified().ok()?;
+    i64::try_from(time.duration_since(UNIX_EPOCH).ok()?.as_nanos()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_covers_manifest_and_shebang() {
+        let args = "Cli { input: \"src/main.rs\", quiet: true }";
+        let base = key(args, Some("[dependencies]\n"), "#!/usr/bin/env cargo");
+        assert_eq!(
+            base,
+            key(args, Some("[dependencies]\n"), "#!/usr/bin/env cargo")
+        );
+        assert_ne!(
+            base,
+            key(
+                args,
+                Some("[dependencies]\nrand = \"0.9\"\n"),
+                "#!/usr/bin/env cargo"
+            )
+        );
+        assert_ne!(base, key(args, None, "#!/usr/bin/env cargo"));
+        assert_ne!(
+            base,
+            key(
+                args,
+                Some("[dependencies]\n"),
+                "#!/usr/bin/env -S cargo +nightly"
+            )
+        );
+        assert_ne!(
+            base,
+            key(
+                "Cli { input: \"src/main.rs\" }",
+                Some("[dependencies]\n"),
+                "#!/usr/bin/env cargo"
+            )
+        );
+    }
+
+    #[test]
+    fn record_goes_stale_with_key_or_sources() {
+        let dir = std::env::temp_dir().join(format!("scriptify-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source, output) = (dir.join("main.rs"), dir.join("script.rs"));
+        std::fs::write(&source, "fn main() {}\n").unwrap();
+        std::fs::write(&output, "fn main() {}\n").unwrap();
+
+        set_tracking(true);
+        track(&source);
+        store(&output, "key").unwrap();
+        assert!(is_fresh(&output, "key"));
+        assert!(!is_fresh(&output, "other key"));
+
+        let file = std::fs::File::options().write(true).open(&source).unwrap();
+        file.set_modified(UNIX_EPOCH).unwrap();
+        assert!(!is_fresh(&output, "key"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
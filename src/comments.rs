@@ -0,0 +1,189 @@
+//! Best-effort preservation of `//` comments, which syn discards and prettyplease
+//! therefore can't print. Whole-line comments directly above an item are carried
+//! through unparsing as marker attributes, then turned back into comments.
+//! Doc comments are attributes already and need no help.
+
+use proc_macro2::Span;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{Attribute, Ident, ImplItem, Item, TraitItem, parse_quote};
+
+const MARKER: &str = "scriptify_comment";
+
+/// Attaches the comments of `source` to the items of `file` parsed from it.
+/// Must run before modules are inlined into `file`, since it matches by line.
+pub fn attach(source: &str, file: &mut syn::File) {
+    let mut attacher = CommentAttacher {
+        lines: source.lines().collect(),
+    };
+    attacher.visit_file_mut(file);
+}
+
+/// Marker attribute that [`restore`] turns into a `//` comment with this text
+pub fn marker(comment: &str) -> Attribute {
+    let marker = Ident::new(MARKER, Span::call_site());
+    parse_quote!(#[#marker = #comment])
+}
+
+/// Turns the marker attributes in unparsed code back into `//` comments
+pub fn restore(code: &str) -> String {
+    let prefix = format!("#[{MARKER} = ");
+
+    code.lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let indent = &line[..line.len() - trimmed.len()];
+            trimmed
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|literal| syn::parse_str::<syn::LitStr>(literal).ok())
+                .map_or_else(
+                    || format!("{line}\n"),
+                    |comment| format!("{indent}//{}\n", comment.value()),
+                )
+        })
+        .collect()
+}
+
+struct CommentAttacher<'a> {
+    lines: Vec<&'a str>,
+}
+
+impl CommentAttacher<'_> {
+    /// Marker attributes for the run of comment lines ending right above `line` (1-based)
+    fn comments_above(&self, line: usize) -> Vec<Attribute> {
+        let mut comments: Vec<&str> = self.lines[..line.saturating_sub(1).min(self.lines.len())]
+            .iter()
+            .rev()
+            .map(|line| line.trim_start())
+            .take_while(|line| line.starts_with("//") && !is_doc_comment(line))
+            .map(|line| &line[2..])
+            .collect();
+        comments.reverse();
+
+        comments.into_iter().map(marker).collect()
+    }
+
+    fn prepend(&self, attrs: &mut Vec<Attribute>, line: usize) {
+        let comments = self.comments_above(line);
+        attrs.splice(0..0, comments);
+    }
+}
+
+fn is_doc_comment(line: &str) -> bool {
+    (line.starts_with("///") && !line.starts_with("////")) || line.starts_with("//!")
+}
+
+impl VisitMut for CommentAttacher<'_> {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        let line = item.span().start().line;
+        if let Some(attrs) = item_attrs(item) {
+            self.prepend(attrs, line);
+        }
+        syn::visit_mut::visit_item_mut(self, item);
+    }
+
+    fn visit_impl_item_mut(&mut self, item: &mut ImplItem) {
+        let line = item.span().start().line;
+        let attrs = match item {
+            ImplItem::Const(item) => &mut item.attrs,
+            ImplItem::Fn(item) => &mut item.attrs,
+            ImplItem::Type(item) => &mut item.attrs,
+            ImplItem::Macro(item) => &mut item.attrs,
+            _ => return,
+        };
+        self.prepend(attrs, line);
+        syn::visit_mut::visit_impl_item_mut(self, item);
+    }
+
+    fn visit_trait_item_mut(&mut self, item: &mut TraitItem) {
+        let line = item.span().start().line;
+        let attrs = match item {
+            TraitItem::Const(item) => &mut item.attrs,
+            TraitItem::Fn(item) => &mut item.attrs,
+            TraitItem::Type(item) => &mut item.attrs,
+            TraitItem::Macro(item) => &mut item.attrs,
+            _ => return,
+        };
+        self.prepend(attrs, line);
+        syn::visit_mut::visit_trait_item_mut(self, item);
+    }
+}
+
+/// The outer attributes of `item`, for the item kinds that have them
+pub fn item_attrs(item: &mut Item) -> Option<&mut Vec<Attribute>> {
+    Some(match item {
+        Item::Const(item) => &mut item.attrs,
+        Item::Enum(item) => &mut item.attrs,
+        Item::ExternCrate(item) => &mut item.attrs,
+        Item::Fn(item) => &mut item.attrs,
+        Item::ForeignMod(item) => &mut item.attrs,
+        Item::Impl(item) => &mut item.attrs,
+        Item::Macro(item) => &mut item.attrs,
+        Item::Mod(item) => &mut item.attrs,
+        Item::Static(item) => &mut item.attrs,
+        Item::Struct(item) => &mut item.attrs,
+        Item::Trait(item) => &mut item.attrs,
+        Item::TraitAlias(item) => &mut item.attrs,
+        Item::Type(item) => &mut item.attrs,
+        Item::Union(item) => &mut item.attrs,
+        Item::Use(item) => &mut item.attrs,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::inline::{InlineOptions, inline_source};
+
+    #[test]
+    fn comments_survive_inlining_across_modules() {
+        let root =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/comments/src/main.rs");
+        let options = InlineOptions {
+            preserve_comments: true,
+            ..InlineOptions::default()
+        };
+        let source = std::fs::read_to_string(&root).unwrap();
+        let (file, errors) = inline_source(&source, &root, &options).unwrap();
+        assert!(errors.is_empty());
+
+        let code = restore(&prettyplease::unparse(&file));
+        assert_eq!(
+            code,
+            r#"// Networking, inlined from net.rs
+mod net {
+    //! Connections
+    // Frame layout, inlined from net/wire.rs
+    pub mod wire {
+        // Bumped on every breaking change
+        // to the frame layout
+        pub const VERSION: u8 = 2;
+    }
+    // Plain TCP for now
+    /// An open connection
+    pub struct Conn {
+        host: String,
+    }
+    impl Conn {
+        // Doesn't connect until first use
+        pub fn open(host: &str) -> Self {
+            Conn { host: host.to_string() }
+        }
+        pub fn describe(&self) -> String {
+            format!("{} (wire v{})", self.host, wire::VERSION)
+        }
+    }
+}
+// Entry point
+fn main() {
+    let conn = net::Conn::open("localhost");
+    println!("{}", conn.describe());
+}
+"#
+        );
+    }
+}
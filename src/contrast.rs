@@ -0,0 +1,76 @@
+//! `--theme-contrast-check`: a rough legibility check of a highlighting theme, using
+//! the WCAG contrast ratio between each highlight color and the theme's background.
+
+use arborium::theme::{Color, Theme};
+
+/// Minimum contrast ratio; WCAG asks 4.5:1 for body text and 3:1 for large text,
+/// and syntax colors are judged by the more lenient bar
+pub const MIN_RATIO: f64 = 3.0;
+
+/// A foreground color that is hard to read on the theme's background
+pub struct LowContrast {
+    /// Highlight categories drawn in this color, e.g. `comment`
+    pub categories: Vec<&'static str>,
+    pub foreground: Color,
+    pub background: Color,
+    pub ratio: f64,
+}
+
+/// Foreground colors of `theme` below [`MIN_RATIO`], worst first. Themes without a
+/// background are measured against black or white, depending on whether they are dark.
+pub fn low_contrast_colors(theme: &Theme) -> Vec<LowContrast> {
+    let background = theme.background.unwrap_or(if theme.is_dark {
+        Color::new(0, 0, 0)
+    } else {
+        Color::new(255, 255, 255)
+    });
+
+    let styles = theme.styles.iter().map(|style| style.fg);
+    let mut low: Vec<LowContrast> = Vec::new();
+    for (category, foreground) in arborium::HIGHLIGHT_NAMES
+        .iter()
+        .zip(styles)
+        .filter_map(|(name, fg)| fg.map(|fg| (*name, fg)))
+        .chain(theme.foreground.map(|fg| ("text", fg)))
+    {
+        let ratio = contrast_ratio(foreground, background);
+        if ratio >= MIN_RATIO {
+            continue;
+        }
+
+        match low.iter_mut().find(|entry| entry.foreground == foreground) {
+            Some(entry) => entry.categories.push(category),
+            None => low.push(LowContrast {
+                categories: vec![category],
+                foreground,
+                background,
+                ratio,
+            }),
+        }
+    }
+
+    low.sort_by(|a, b| a.ratio.total_cmp(&b.ratio));
+    low
+}
+
+pub fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn contrast_ratio(a: Color, b: Color) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Relative luminance as defined by WCAG 2
+fn luminance(color: Color) -> f64 {
+    let channel = |value: u8| {
+        let value = f64::from(value) / 255.0;
+        if value <= 0.039_28 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r) + 0.7152 * channel(color.g) + 0.0722 * channel(color.b)
+}
@@ -0,0 +1,187 @@
+//! `--emit-deps-json`: finds the external crates the inlined code refers to. A syntactic
+//! scan, so it errs on the side of missing a crate rather than inventing one: only
+//! `extern crate`s, `use` roots and the first segment of multi-segment paths count, and
+//! names defined or imported anywhere in the file are never treated as crates.
+
+use std::collections::{BTreeSet, HashSet};
+
+use syn::visit::Visit;
+use syn::{Item, ItemExternCrate, ItemUse, UseTree};
+
+/// Crates that ship with the toolchain and never need a manifest entry
+const BUILTIN_CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// Path roots that refer to the current crate, a tool attribute or a primitive type
+const NON_CRATE_ROOTS: &[&str] = &[
+    "crate",
+    "self",
+    "super",
+    "Self",
+    "clippy",
+    "rustfmt",
+    "diagnostic",
+    "bool",
+    "char",
+    "str",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "f32",
+    "f64",
+];
+
+/// The external crates referenced by `file`, sorted by name
+pub fn external_crates(file: &syn::File) -> BTreeSet<String> {
+    let mut locals = LocalNames::default();
+    locals.visit_file(file);
+
+    let mut finder = CrateFinder {
+        locals: &locals.names,
+        crates: BTreeSet::new(),
+    };
+    finder.visit_file(file);
+    finder.crates
+}
+
+/// Names the file defines or imports, which shadow any crate of the same name
+#[derive(Default)]
+struct LocalNames {
+    names: HashSet<String>,
+}
+
+impl<'ast> Visit<'ast> for LocalNames {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let ident = match item {
+            Item::Const(item) => Some(&item.ident),
+            Item::Enum(item) => Some(&item.ident),
+            Item::Fn(item) => Some(&item.sig.ident),
+            Item::Macro(item) => item.ident.as_ref(),
+            Item::Mod(item) => Some(&item.ident),
+            Item::Static(item) => Some(&item.ident),
+            Item::Struct(item) => Some(&item.ident),
+            Item::Trait(item) => Some(&item.ident),
+            Item::TraitAlias(item) => Some(&item.ident),
+            Item::Type(item) => Some(&item.ident),
+            Item::Union(item) => Some(&item.ident),
+            _ => None,
+        };
+        if let Some(ident) = ident {
+            self.names.insert(ident.to_string());
+        }
+        syn::visit::visit_item(self, item);
+    }
+
+    fn visit_item_use(&mut self, item: &'ast ItemUse) {
+        self.imported(&item.tree, false);
+    }
+
+    fn visit_generic_param(&mut self, param: &'ast syn::GenericParam) {
+        if let syn::GenericParam::Type(param) = param {
+            self.names.insert(param.ident.to_string());
+        }
+        syn::visit::visit_generic_param(self, param);
+    }
+}
+
+impl LocalNames {
+    /// Adds the names a use tree binds, except a bare `use serde;` which names the crate
+    fn imported(&mut self, tree: &UseTree, nested: bool) {
+        match tree {
+            UseTree::Path(path) => self.imported(&path.tree, true),
+            UseTree::Name(name) if nested && name.ident != "self" => {
+                self.names.insert(name.ident.to_string());
+            }
+            UseTree::Rename(rename) => {
+                self.names.insert(rename.rename.to_string());
+            }
+            UseTree::Group(group) => group
+                .items
+                .iter()
+                .for_each(|tree| self.imported(tree, nested)),
+            UseTree::Name(_) | UseTree::Glob(_) => {}
+        }
+    }
+}
+
+struct CrateFinder<'a> {
+    locals: &'a HashSet<String>,
+    crates: BTreeSet<String>,
+}
+
+impl CrateFinder<'_> {
+    fn record(&mut self, root: &syn::Ident, absolute: bool) {
+        let name = root.to_string();
+        if BUILTIN_CRATES.contains(&name.as_str()) {
+            return;
+        }
+        // Crate names are lowercase by convention, so capitalized roots are types or
+        // enum names brought in by glob imports
+        let plausible = !NON_CRATE_ROOTS.contains(&name.as_str())
+            && !name.chars().any(char::is_uppercase)
+            && !self.locals.contains(&name);
+        if absolute || plausible {
+            self.crates.insert(name);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for CrateFinder<'_> {
+    fn visit_item_extern_crate(&mut self, item: &'ast ItemExternCrate) {
+        if item.ident != "self" {
+            self.record(&item.ident, true);
+        }
+    }
+
+    fn visit_item_use(&mut self, item: &'ast ItemUse) {
+        let absolute = item.leading_colon.is_some();
+        let mut roots = Vec::new();
+        use_roots(&item.tree, &mut roots);
+        for root in roots {
+            self.record(root, absolute);
+        }
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        if let Some(first) = path.segments.first()
+            && (path.leading_colon.is_some() || path.segments.len() > 1)
+        {
+            self.record(&first.ident, path.leading_colon.is_some());
+        }
+        syn::visit::visit_path(self, path);
+    }
+
+    fn visit_expr_path(&mut self, expr: &'ast syn::ExprPath) {
+        match &expr.qself {
+            // `<T>::f` continues a type, so its path has no root of its own
+            Some(qself) if qself.position == 0 => self.visit_type(&qself.ty),
+            _ => syn::visit::visit_expr_path(self, expr),
+        }
+    }
+
+    fn visit_type_path(&mut self, ty: &'ast syn::TypePath) {
+        match &ty.qself {
+            Some(qself) if qself.position == 0 => self.visit_type(&qself.ty),
+            _ => syn::visit::visit_type_path(self, ty),
+        }
+    }
+}
+
+/// The first segment of each path in a (possibly grouped) use tree
+fn use_roots<'a>(tree: &'a UseTree, roots: &mut Vec<&'a syn::Ident>) {
+    match tree {
+        UseTree::Path(path) => roots.push(&path.ident),
+        UseTree::Name(name) => roots.push(&name.ident),
+        UseTree::Rename(rename) => roots.push(&rename.ident),
+        UseTree::Group(group) => group.items.iter().for_each(|tree| use_roots(tree, roots)),
+        UseTree::Glob(_) => {}
+    }
+}
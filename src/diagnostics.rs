@@ -0,0 +1,111 @@
+//! Process-wide warning and error reporting, so any stage of the pipeline can emit
+//! warnings without threading a collector through every function.
+
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+static JSON: AtomicBool = AtomicBool::new(false);
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const CYAN: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+
+/// Suppresses printing of warnings (they are still counted)
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Colors the `error:`/`warning:` labels of diagnostics on stderr
+pub fn set_color(color: bool) {
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// Prints diagnostics as JSON lines (`{"level":..,"message":..,"file":..,"line":..,"col":..}`)
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Source position a diagnostic refers to
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub fn warn(message: impl Display) {
+    warn_at(None, message);
+}
+
+pub fn warn_at(location: Option<Location>, message: impl Display) {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    if !QUIET.load(Ordering::Relaxed) {
+        emit("warning", YELLOW, location, &message.to_string());
+    }
+}
+
+/// Informational message; hidden by --quiet but not counted as a warning
+pub fn note(message: impl Display) {
+    if !QUIET.load(Ordering::Relaxed) {
+        emit("note", CYAN, None, &message.to_string());
+    }
+}
+
+/// Reports a fatal error (never silenced by quiet mode)
+pub fn error(location: Option<Location>, message: impl Display) {
+    emit("error", RED, location, &message.to_string());
+}
+
+fn emit(level: &str, color: &str, location: Option<Location>, message: &str) {
+    if !JSON.load(Ordering::Relaxed) {
+        eprintln!("{}: {message}", label(level, color));
+        return;
+    }
+
+    let (file, line, column) = match location {
+        Some(location) => (
+            json_string(&location.file.display().to_string()),
+            location.line.to_string(),
+            location.column.to_string(),
+        ),
+        None => ("null".to_string(), "null".to_string(), "null".to_string()),
+    };
+    eprintln!(
+        "{{\"level\":{},\"message\":{},\"file\":{file},\"line\":{line},\"col\":{column}}}",
+        json_string(level),
+        json_string(message)
+    );
+}
+
+fn label(name: &str, color: &str) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        format!("{color}{name}{RESET}")
+    } else {
+        name.to_string()
+    }
+}
+
+pub fn warning_count() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+/// Quotes and escapes `value` as a JSON string
+pub fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
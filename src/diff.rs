@@ -0,0 +1,134 @@
+//! Line-based unified diff (via `similar`) for comparing generated output with an
+//! existing file, and its terminal coloring.
+
+use similar::TextDiff;
+
+use crate::lines::ansi_lines;
+
+const CONTEXT: usize = 3;
+
+/// Returns a unified diff between `old` and `new`, or `None` if they are identical
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    Some(
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(CONTEXT)
+            .header(old_label, new_label)
+            .to_string(),
+    )
+}
+
+/// Colors diff markers red/green (and hunk headers cyan) for terminal display
+pub fn colorize(diff: &str) -> String {
+    diff.split_inclusive('\n')
+        .map(|line| {
+            let color = match line.as_bytes().first() {
+                Some(b'+') => "\x1b[32m",
+                Some(b'-') => "\x1b[31m",
+                Some(b'@') => "\x1b[36m",
+                _ => return line.to_string(),
+            };
+            let (text, newline) = line
+                .strip_suffix('\n')
+                .map_or((line, ""), |text| (text, "\n"));
+            format!("{color}{text}\x1b[0m{newline}")
+        })
+        .collect()
+}
+
+/// Colors a diff like `colorize`, but shows each line's content as it appears in
+/// `old_highlighted`/`new_highlighted`: the compared texts highlighted as a whole, so
+/// tokens spanning lines keep their colors. The `+`/`-` markers are drawn inverted so they
+/// stand out from any syntax color.
+pub fn colorize_highlighted(diff: &str, old_highlighted: &str, new_highlighted: &str) -> String {
+    let old_lines = ansi_lines(old_highlighted);
+    let new_lines = ansi_lines(new_highlighted);
+    let (mut old_line, mut new_line) = (0, 0);
+
+    let mut out = String::with_capacity(diff.len() * 2);
+    for (i, line) in diff.lines().enumerate() {
+        let content = |lines: &[String], number: usize| {
+            lines
+                .get(number)
+                .cloned()
+                .unwrap_or_else(|| line[1..].to_string())
+        };
+        match line.as_bytes().first() {
+            // The `---`/`+++` file labels
+            Some(b'-' | b'+') if i < 2 => out.push_str(&format!("\x1b[1m{line}\x1b[0m")),
+            Some(b'@') => {
+                (old_line, new_line) = hunk_start(line);
+                out.push_str(&format!("\x1b[36m{line}\x1b[0m"));
+            }
+            Some(b'-') => {
+                out.push_str("\x1b[1;7;31m-\x1b[0m");
+                out.push_str(&content(&old_lines, old_line));
+                old_line += 1;
+            }
+            Some(b'+') => {
+                out.push_str("\x1b[1;7;32m+\x1b[0m");
+                out.push_str(&content(&new_lines, new_line));
+                new_line += 1;
+            }
+            Some(b' ') => {
+                out.push(' ');
+                out.push_str(&content(&new_lines, new_line));
+                old_line += 1;
+                new_line += 1;
+            }
+            // `\ No newline at end of file`
+            _ => out.push_str(&format!("\x1b[2m{line}\x1b[0m")),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Zero-based first old and new line of a `@@ -a,b +c,d @@` hunk header (an empty range
+/// names the line before it)
+fn hunk_start(header: &str) -> (usize, usize) {
+    let mut ranges = header.split_whitespace().skip(1).take(2).map(|range| {
+        let (start, len) = range[1..].split_once(',').unwrap_or((&range[1..], "1"));
+        let start: usize = start.parse().unwrap_or(0);
+        if len == "0" {
+            start
+        } else {
+            start.saturating_sub(1)
+        }
+    });
+    (ranges.next().unwrap_or(0), ranges.next().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_matches_diff_u() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj";
+        assert_eq!(
+            unified_diff(old, new, "old", "new").as_deref(),
+            Some(concat!(
+                "--- old\n+++ new\n",
+                "@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n",
+                "@@ -7,4 +7,4 @@\n g\n h\n i\n-j\n+j\n\\ No newline at end of file\n",
+            ))
+        );
+        assert_eq!(unified_diff(old, old, "old", "new"), None);
+    }
+
+    #[test]
+    fn hunk_start_reads_similar_headers() {
+        let diff = unified_diff("a\n", "a\nb\n", "old", "new").unwrap();
+        let header = diff.lines().nth(2).unwrap();
+        assert_eq!(hunk_start(header), (0, 0));
+
+        let diff = unified_diff("", "a\n", "old", "new").unwrap();
+        assert_eq!(hunk_start(diff.lines().nth(2).unwrap()), (0, 0));
+    }
+}
@@ -0,0 +1,54 @@
+//! `--drop-empty-mods`: removes inlined modules that have no items, e.g. files with only
+//! comments or modules emptied by other passes, so the output isn't littered with `mod foo {}`.
+
+use std::collections::HashSet;
+
+use syn::Item;
+
+use crate::strip_private::mentions;
+
+/// What was dropped: the module paths, and those whose name is still mentioned elsewhere
+pub struct Dropped {
+    pub removed: Vec<String>,
+    pub referenced: Vec<String>,
+}
+
+/// Removes inline modules without items from `file`, innermost first, so a module that
+/// only contains empty modules is dropped as well. `mod foo;` declarations that weren't
+/// inlined are left alone.
+pub fn drop_empty_mods(file: &mut syn::File) -> Dropped {
+    let mut removed = Vec::new();
+    drop_in(&mut file.items, "crate", &mut removed);
+
+    let mentioned: HashSet<String> = file.items.iter().flat_map(mentions).collect();
+    let referenced = removed
+        .iter()
+        .filter(|path| {
+            let name = path.rsplit("::").next().unwrap_or(path);
+            mentioned.contains(name)
+        })
+        .cloned()
+        .collect();
+
+    Dropped {
+        removed,
+        referenced,
+    }
+}
+
+fn drop_in(items: &mut Vec<Item>, parent: &str, removed: &mut Vec<String>) {
+    items.retain_mut(|item| {
+        let Item::Mod(module) = item else { return true };
+        let Some((_, content)) = &mut module.content else {
+            return true;
+        };
+
+        let path = format!("{parent}::{}", module.ident);
+        drop_in(content, &path, removed);
+        if content.is_empty() {
+            removed.push(path);
+            return false;
+        }
+        true
+    });
+}
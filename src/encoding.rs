@@ -0,0 +1,106 @@
+//! `--input-encoding`: decodes source files in legacy encodings to the UTF-8 syn parses.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::{Result, ScriptifyError};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    /// UTF-8, the encoding rustc reads
+    #[default]
+    #[value(name = "utf-8")]
+    Utf8,
+    /// ISO-8859-1, where every byte is the code point of the same value
+    Latin1,
+    /// Little-endian unless the file starts with a big-endian byte order mark
+    #[value(name = "utf-16")]
+    Utf16,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf16 => "UTF-16",
+        })
+    }
+}
+
+/// Reads the file at `path` as `encoding`
+pub fn read_to_string(path: &Path, encoding: Encoding) -> Result<String> {
+    let bytes = std::fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ScriptifyError::InputNotFound(path.to_path_buf()),
+        _ => ScriptifyError::io(format!("failed to read '{}'", path.display()))(e),
+    })?;
+    decode(bytes, encoding)
+        .map_err(|reason| format!("'{}' is not valid {encoding}: {reason}", path.display()).into())
+}
+
+/// Converts `bytes` in `encoding` to a string, describing the first invalid sequence
+pub fn decode(bytes: Vec<u8>, encoding: Encoding) -> std::result::Result<String, String> {
+    match encoding {
+        Encoding::Utf8 => decode_strict(encoding_rs::UTF_8, &bytes)
+            .map_err(|offset| format!("invalid byte at offset {offset} (try --input-encoding)")),
+        Encoding::Latin1 => Ok(encoding_rs::mem::decode_latin1(&bytes).into_owned()),
+        Encoding::Utf16 => {
+            let (codec, bom_length) = match encoding_rs::Encoding::for_bom(&bytes) {
+                Some((codec, length)) if codec != encoding_rs::UTF_8 => (codec, length),
+                _ => (encoding_rs::UTF_16LE, 0),
+            };
+            decode_strict(codec, &bytes[bom_length..])
+                .map_err(|offset| format!("invalid code unit at byte {}", bom_length + offset))
+        }
+    }
+}
+
+/// Decodes `bytes` without replacement characters or BOM sniffing, returning the byte
+/// offset of the first malformed sequence on failure
+fn decode_strict(
+    codec: &'static encoding_rs::Encoding,
+    bytes: &[u8],
+) -> std::result::Result<String, usize> {
+    let mut decoder = codec.new_decoder_without_bom_handling();
+    let capacity = decoder
+        .max_utf8_buffer_length_without_replacement(bytes.len())
+        .unwrap_or(bytes.len() * 3);
+    let mut decoded = String::with_capacity(capacity);
+    match decoder.decode_to_string_without_replacement(bytes, &mut decoded, true) {
+        (encoding_rs::DecoderResult::InputEmpty, _) => Ok(decoded),
+        (encoding_rs::DecoderResult::Malformed(bad, after), read) => {
+            Err(read - after as usize - bad as usize)
+        }
+        // The buffer is sized for the whole input up front
+        (encoding_rs::DecoderResult::OutputFull, read) => Err(read),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_utf16_by_byte_order_mark() {
+        let little = vec![0xFF, 0xFE, b'f', 0, b'n', 0];
+        assert_eq!(decode(little, Encoding::Utf16).unwrap(), "fn");
+        let big = vec![0xFE, 0xFF, 0, b'f', 0, b'n'];
+        assert_eq!(decode(big, Encoding::Utf16).unwrap(), "fn");
+        let unmarked = vec![b'f', 0, b'n', 0];
+        assert_eq!(decode(unmarked, Encoding::Utf16).unwrap(), "fn");
+        // An unpaired high surrogate
+        assert!(decode(vec![0x00, 0xD8, b'f', 0], Encoding::Utf16).is_err());
+    }
+
+    #[test]
+    fn reports_where_decoding_fails() {
+        assert_eq!(
+            decode(b"fn \xFF".to_vec(), Encoding::Utf8).unwrap_err(),
+            "invalid byte at offset 3 (try --input-encoding)"
+        );
+        assert_eq!(
+            decode(vec![b'a', 0xE9], Encoding::Latin1).unwrap(),
+            "a\u{e9}"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::diagnostics::Location;
+
+/// Everything that can go wrong while generating a script
+#[derive(Debug)]
+pub enum ScriptifyError {
+    /// Missing arguments or an invalid combination of options
+    Usage(String),
+    /// The input file or directory does not exist
+    InputNotFound(PathBuf),
+    /// The entry point is not valid Rust
+    ParseError {
+        file: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A Cargo.toml could not be read, parsed or used to locate an entry point
+    ManifestError(String),
+    /// The requested highlighting theme does not exist
+    ThemeNotFound {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// Reading or writing a file failed
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+    /// The --hook command exited unsuccessfully, with the exit code to pass on
+    HookFailed { command: String, code: u8 },
+    /// Any other failure, described by its message
+    Other(String),
+}
+
+impl ScriptifyError {
+    /// Wraps an I/O error with a description of what was being attempted, for use with `map_err`
+    pub fn io(context: impl Into<String>) -> impl FnOnce(std::io::Error) -> Self {
+        let context = context.into();
+        move |source| Self::Io { context, source }
+    }
+
+    /// Source position the error refers to, if any
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Self::ParseError {
+                file, line, column, ..
+            } => Some(Location {
+                file: file.clone(),
+                line: *line,
+                column: *column,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Process exit code reported for this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::ParseError { .. } => 3,
+            Self::ManifestError(_) => 4,
+            Self::ThemeNotFound { .. } => 5,
+            Self::HookFailed { code, .. } => *code,
+            Self::InputNotFound(_) | Self::Io { .. } | Self::Other(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for ScriptifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InputNotFound(path) => write!(f, "input '{}' does not exist", path.display()),
+            Self::ParseError {
+                file,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "failed to parse '{}' at {line}:{column}: {message}",
+                file.display()
+            ),
+            Self::Usage(message) | Self::ManifestError(message) | Self::Other(message) => {
+                write!(f, "{message}")
+            }
+            Self::ThemeNotFound { name, suggestion } => {
+                write!(f, "unknown theme '{name}'.")?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, " Did you mean '{suggestion}'?")?;
+                }
+                write!(f, " Use --list-themes to see available themes")
+            }
+            Self::Io { context, source } => write!(f, "{context}: {source}"),
+            Self::HookFailed { command, code } => {
+                write!(f, "hook `{command}` failed with exit code {code}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScriptifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ScriptifyError {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io {
+            context: "I/O error".to_string(),
+            source,
+        }
+    }
+}
+
+impl From<toml::de::Error> for ScriptifyError {
+    fn from(error: toml::de::Error) -> Self {
+        Self::ManifestError(format!("invalid Cargo.toml: {error}"))
+    }
+}
+
+impl From<String> for ScriptifyError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<&str> for ScriptifyError {
+    fn from(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+}
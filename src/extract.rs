@@ -0,0 +1,106 @@
+//! `--only-module`: pulls one module subtree out of the inlined crate so it can be
+//! emitted as a standalone file.
+
+use syn::visit::Visit;
+use syn::{AttrStyle, Item, ItemMod, UsePath};
+
+use crate::{Result, ScriptifyError};
+
+/// Replaces `file` with the contents of the module at `module_path` (e.g. `net::http`)
+pub fn extract_module(file: syn::File, module_path: &str) -> Result<syn::File> {
+    let mut items = file.items;
+    let mut module = None;
+
+    for segment in module_path.trim_start_matches("crate::").split("::") {
+        let found = items.into_iter().find_map(|item| match item {
+            Item::Mod(item) if item.ident == segment => Some(item),
+            _ => None,
+        });
+        let Some(found) = found else {
+            return Err(ScriptifyError::Usage(format!(
+                "module `{module_path}` not found"
+            )));
+        };
+        let Some((_, content)) = found.content.clone() else {
+            return Err(ScriptifyError::Usage(format!(
+                "module `{module_path}` could not be inlined (`{segment}` was left as `mod {segment};`)"
+            )));
+        };
+
+        items = content;
+        module = Some(found);
+    }
+
+    let attrs = module
+        .map(|module| {
+            module
+                .attrs
+                .into_iter()
+                .filter(|attr| matches!(attr.style, AttrStyle::Inner(_)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(syn::File {
+        shebang: None,
+        attrs,
+        items,
+    })
+}
+
+/// Whether the extracted module refers to items outside itself through `crate::` paths
+/// or `super::` paths that climb above its root
+pub fn has_outside_references(file: &syn::File) -> bool {
+    let mut finder = OutsideReferences::default();
+    finder.visit_file(file);
+    finder.found
+}
+
+#[derive(Default)]
+struct OutsideReferences {
+    /// Nesting depth below the extracted module
+    depth: usize,
+    found: bool,
+}
+
+impl OutsideReferences {
+    fn check<'a>(&mut self, segments: impl Iterator<Item = &'a syn::Ident>) {
+        let mut supers = 0;
+        for ident in segments {
+            if ident == "crate" {
+                self.found = true;
+                return;
+            }
+            if ident != "super" {
+                break;
+            }
+            supers += 1;
+        }
+
+        self.found |= supers > self.depth;
+    }
+}
+
+impl<'ast> Visit<'ast> for OutsideReferences {
+    fn visit_item_mod(&mut self, item: &'ast ItemMod) {
+        self.depth += 1;
+        syn::visit::visit_item_mod(self, item);
+        self.depth -= 1;
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        self.check(path.segments.iter().map(|segment| &segment.ident));
+        syn::visit::visit_path(self, path);
+    }
+
+    fn visit_use_path(&mut self, path: &'ast UsePath) {
+        let mut idents = vec![&path.ident];
+        let mut tree = &*path.tree;
+        while let syn::UseTree::Path(next) = tree {
+            idents.push(&next.ident);
+            tree = &next.tree;
+        }
+
+        self.check(idents.into_iter());
+    }
+}
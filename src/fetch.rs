@@ -0,0 +1,37 @@
+//! Fetching remote source files for URL inputs, through the system `curl`.
+
+use std::process::Command;
+
+use crate::{Result, ScriptifyError};
+
+const MAX_REDIRECTS: &str = "10";
+
+/// Whether `input` names an HTTP(S) URL rather than a local path
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("https://") || input.starts_with("http://")
+}
+
+/// Downloads `url`, following redirects and failing on non-2xx responses
+pub fn fetch(url: &str) -> Result<String> {
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--max-redirs",
+            MAX_REDIRECTS,
+            "--",
+            url,
+        ])
+        .output()
+        .map_err(ScriptifyError::io("failed to run curl"))?;
+
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr);
+        let reason = reason.trim().trim_start_matches("curl: ");
+        return Err(format!("failed to fetch '{url}': {reason}").into());
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| format!("'{url}' is not valid UTF-8").into())
+}
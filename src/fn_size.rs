@@ -0,0 +1,115 @@
+//! `--warn-large-functions`: measures every function in the inlined crate by the number of
+//! statements in its body, to point out refactoring candidates in a large script.
+
+use std::fmt;
+
+use syn::visit::Visit;
+use syn::{Block, ImplItemFn, ItemFn, ItemImpl, ItemMod, ItemTrait, TraitItemFn};
+
+/// A function and the number of statements in its body, nested blocks and closures
+/// included but nested items not
+pub struct FunctionSize {
+    pub name: String,
+    pub statements: usize,
+}
+
+impl fmt::Display for FunctionSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fn `{}` has {} statements", self.name, self.statements)
+    }
+}
+
+/// The functions in `file` with more than `limit` statements, largest first
+pub fn large_functions(file: &syn::File, limit: usize) -> Vec<FunctionSize> {
+    let mut finder = FunctionFinder {
+        path: vec!["crate".to_string()],
+        sizes: Vec::new(),
+    };
+    finder.visit_file(file);
+
+    let mut large: Vec<FunctionSize> = finder
+        .sizes
+        .into_iter()
+        .filter(|size| size.statements > limit)
+        .collect();
+    large.sort_by(|a, b| {
+        b.statements
+            .cmp(&a.statements)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    large
+}
+
+struct FunctionFinder {
+    /// Module path, plus the type or trait while inside an impl or trait
+    path: Vec<String>,
+    sizes: Vec<FunctionSize>,
+}
+
+impl FunctionFinder {
+    fn record(&mut self, ident: &syn::Ident, body: &Block) {
+        let mut counter = StatementCounter(0);
+        counter.visit_block(body);
+        self.sizes.push(FunctionSize {
+            name: format!("{}::{ident}", self.path.join("::")),
+            statements: counter.0,
+        });
+    }
+
+    fn within<'ast, T>(&mut self, segment: String, node: &'ast T, visit: fn(&mut Self, &'ast T)) {
+        self.path.push(segment);
+        visit(self, node);
+        self.path.pop();
+    }
+}
+
+impl<'ast> Visit<'ast> for FunctionFinder {
+    fn visit_item_mod(&mut self, item: &'ast ItemMod) {
+        self.within(item.ident.to_string(), item, syn::visit::visit_item_mod);
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast ItemImpl) {
+        let ty = match &*item.self_ty {
+            syn::Type::Path(ty) => ty.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        };
+        let segment = ty.unwrap_or_else(|| "<impl>".to_string());
+        self.within(segment, item, syn::visit::visit_item_impl);
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast ItemTrait) {
+        self.within(item.ident.to_string(), item, syn::visit::visit_item_trait);
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast ItemFn) {
+        self.record(&item.sig.ident, &item.block);
+        let segment = item.sig.ident.to_string();
+        self.within(segment, item, syn::visit::visit_item_fn);
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast ImplItemFn) {
+        self.record(&item.sig.ident, &item.block);
+        let segment = item.sig.ident.to_string();
+        self.within(segment, item, syn::visit::visit_impl_item_fn);
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast TraitItemFn) {
+        if let Some(body) = &item.default {
+            self.record(&item.sig.ident, body);
+        }
+        let segment = item.sig.ident.to_string();
+        self.within(segment, item, syn::visit::visit_trait_item_fn);
+    }
+}
+
+/// Counts statements, without descending into nested items (each is measured on its own)
+struct StatementCounter(usize);
+
+impl<'ast> Visit<'ast> for StatementCounter {
+    fn visit_stmt(&mut self, stmt: &'ast syn::Stmt) {
+        self.0 += 1;
+        syn::visit::visit_stmt(self, stmt);
+    }
+
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
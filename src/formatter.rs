@@ -0,0 +1,236 @@
+//! `--fmt-backend`: turns the inlined syntax tree into code, either with prettyplease
+//! (built in) or by running the system `rustfmt` over prettyplease's output.
+//! prettyplease takes no edition, so `--edition` only reaches rustfmt; it prints whatever
+//! syn parses, except items syn keeps as raw tokens. Of those, the edition 2024 `safe fn`/
+//! `safe static` in `unsafe extern` blocks are printed here, and anything else is reported
+//! instead of panicking. The `edition2024` fixture guards the newer syntax.
+
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Command, Stdio};
+use std::sync::Once;
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{ForeignItem, Ident, parse_quote};
+
+use crate::{Result, ScriptifyError, diagnostics};
+
+/// Marks a foreign item that was `safe` before the keyword was taken out for printing
+const SAFE_MARKER: &str = "scriptify_safe";
+
+pub trait Formatter {
+    fn format(&self, file: &syn::File) -> Result<String>;
+}
+
+pub struct Prettyplease;
+
+impl Formatter for Prettyplease {
+    fn format(&self, file: &syn::File) -> Result<String> {
+        unparse(file)
+    }
+}
+
+/// Runs `rustfmt`, which picks up `rustfmt.toml` from the current directory upwards
+pub struct Rustfmt<'a> {
+    pub edition: &'a str,
+    /// Turn off rustfmt's sorting of `use` items and `mod` declarations
+    pub keep_order: bool,
+}
+
+impl Formatter for Rustfmt<'_> {
+    fn format(&self, file: &syn::File) -> Result<String> {
+        static MISSING: Once = Once::new();
+
+        let code = unparse(file)?;
+        let mut command = Command::new("rustfmt");
+        command.args(["--edition", self.edition]);
+        if self.keep_order {
+            command.args(["--config", "reorder_imports=false,reorder_modules=false"]);
+        }
+        let child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                MISSING.call_once(|| {
+                    diagnostics::warn(
+                        "rustfmt was not found, formatting with prettyplease instead",
+                    );
+                });
+                return Ok(code);
+            }
+            Err(e) => return Err(ScriptifyError::io("failed to run rustfmt")(e)),
+        };
+
+        // Written from a separate thread so a large file can't deadlock against a full
+        // stdout pipe
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = code.clone();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child
+            .wait_with_output()
+            .map_err(ScriptifyError::io("failed to run rustfmt"))?;
+        let _ = writer.join();
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("rustfmt failed ({}): {}", output.status, stderr.trim()).into());
+        }
+
+        String::from_utf8(output.stdout).map_err(|_| "rustfmt produced invalid UTF-8".into())
+    }
+}
+
+/// prettyplease's output for `file`, with `safe` foreign items restored
+pub fn unparse(file: &syn::File) -> Result<String> {
+    let mut finder = VerbatimFinder(false);
+    finder.visit_file(file);
+    if !finder.0 {
+        return print(file);
+    }
+
+    let mut file = file.clone();
+    SafeItems.visit_file_mut(&mut file);
+    print(&file).map(|code| restore_safe(&code))
+}
+
+/// Runs prettyplease, reporting syntax it can't print (where it panics) as an error
+fn print(file: &syn::File) -> Result<String> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let printed = panic::catch_unwind(AssertUnwindSafe(|| prettyplease::unparse(file)));
+    panic::set_hook(hook);
+
+    printed.map_err(|payload| {
+        let reason = payload
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| payload.downcast_ref::<&str>().copied())
+            .unwrap_or("unknown reason");
+        ScriptifyError::Other(format!("prettyplease can't print this code: {reason}"))
+    })
+}
+
+/// Whether the file has foreign items syn only keeps as raw tokens
+struct VerbatimFinder(bool);
+
+impl<'ast> Visit<'ast> for VerbatimFinder {
+    fn visit_foreign_item(&mut self, item: &'ast ForeignItem) {
+        self.0 |= matches!(item, ForeignItem::Verbatim(_));
+        syn::visit::visit_foreign_item(self, item);
+    }
+}
+
+/// Parses `safe` foreign items without the keyword, marking them so it can be put back
+struct SafeItems;
+
+impl VisitMut for SafeItems {
+    fn visit_foreign_item_mut(&mut self, item: &mut ForeignItem) {
+        if let ForeignItem::Verbatim(tokens) = item
+            && let Some(stripped) = without_safe(tokens.clone())
+            && let Ok(mut parsed) = syn::parse2::<ForeignItem>(stripped)
+        {
+            let marker = Ident::new(SAFE_MARKER, Span::call_site());
+            match &mut parsed {
+                ForeignItem::Fn(function) => function.attrs.push(parse_quote!(#[#marker])),
+                ForeignItem::Static(item) => item.attrs.push(parse_quote!(#[#marker])),
+                _ => return,
+            }
+            *item = parsed;
+        }
+        syn::visit_mut::visit_foreign_item_mut(self, item);
+    }
+}
+
+/// `tokens` without their top-level `safe` keyword, if they have one
+fn without_safe(tokens: TokenStream) -> Option<TokenStream> {
+    let mut found = false;
+    let rest = tokens
+        .into_iter()
+        .filter(|token| {
+            let is_safe = !found && matches!(token, TokenTree::Ident(ident) if ident == "safe");
+            found |= is_safe;
+            !is_safe
+        })
+        .collect();
+    found.then_some(rest)
+}
+
+/// Drops the marker lines and puts `safe` back before the `fn` or `static` of the next line
+fn restore_safe(code: &str) -> String {
+    let marker = format!("#[{SAFE_MARKER}]");
+    let mut restored = String::with_capacity(code.len());
+    let mut pending = false;
+
+    for line in code.lines() {
+        if line.trim() == marker {
+            pending = true;
+            continue;
+        }
+        match line.find("fn ").or_else(|| line.find("static ")) {
+            Some(at) if pending => {
+                restored.push_str(&line[..at]);
+                restored.push_str("safe ");
+                restored.push_str(&line[at..]);
+            }
+            _ => restored.push_str(line),
+        }
+        pending = false;
+        restored.push('\n');
+    }
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> syn::File {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/edition2024/src/main.rs");
+        syn::parse_file(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn edition_2024_syntax_round_trips() {
+        let file = fixture();
+        let code = Prettyplease.format(&file).unwrap();
+        let reparsed = syn::parse_file(&code).unwrap();
+        assert_eq!(reparsed, file);
+        assert_eq!(Prettyplease.format(&reparsed).unwrap(), code);
+    }
+
+    #[test]
+    fn edition_2024_syntax_is_printed_as_written() {
+        let code = Prettyplease.format(&fixture()).unwrap();
+        for construct in [
+            "unsafe extern \"C\" {",
+            "pub safe fn abs(x: i32) -> i32;",
+            "pub safe static environ: *const *const u8;",
+            "pub fn labs(x: i64) -> i64;",
+            "#[unsafe(no_mangle)]",
+            "+ use<'a>",
+            "&raw const number",
+            "async |x: u8|",
+            "&& let text = value.to_string()",
+            "let r#gen =",
+        ] {
+            assert!(
+                code.contains(construct),
+                "missing `{construct}` in:\n{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn unprintable_syntax_is_an_error() {
+        let file = syn::parse_file("trait T {\n    default fn f();\n}\n").unwrap();
+        let error = Prettyplease.format(&file).unwrap_err();
+        assert!(error.to_string().contains("TraitItem::Verbatim"), "{error}");
+    }
+}
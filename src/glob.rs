@@ -0,0 +1,195 @@
+//! Glob expansion for `--input-glob`: `globset` matches (`*`, `?`, `[...]`, `**` and
+//! `{a,b}`) the files `ignore` lists below the pattern's literal prefix. Patterns always
+//! use `/` as the separator. Wildcards don't match hidden entries, nor entries excluded by
+//! `.gitignore` unless ignore rules are disabled.
+
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobBuilder};
+use ignore::WalkBuilder;
+
+use crate::Result;
+
+/// Returns the sorted, de-duplicated list of files matching `pattern`
+pub fn expand(pattern: &str, respect_ignore: bool) -> Result<Vec<PathBuf>> {
+    let (base, glob) = split_base(pattern);
+    if glob.is_empty() {
+        return Ok(base.is_file().then_some(base).into_iter().collect());
+    }
+
+    let matcher = GlobBuilder::new(glob)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| format!("invalid glob pattern '{pattern}': {e}"))?
+        .compile_matcher();
+    // Hidden entries are only reached when the pattern names them
+    let literals: Vec<String> = glob
+        .split('/')
+        .filter(|c| !has_wildcards(c))
+        .map(str::to_string)
+        .collect();
+    let max_depth = if glob.contains("**") {
+        usize::MAX
+    } else {
+        glob.split('/').filter(|c| !c.is_empty()).count()
+    };
+    let root = if base.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        base.as_path()
+    };
+
+    let mut matches: Vec<PathBuf> = walker(root, respect_ignore)
+        .max_depth(Some(max_depth))
+        .follow_links(true)
+        .filter_entry(move |entry| {
+            !entry.file_name().to_str().is_some_and(|name| {
+                name.starts_with('.') && !literals.iter().any(|literal| literal == name)
+            })
+        })
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(root).ok()?;
+            matcher.is_match(relative).then(|| base.join(relative))
+        })
+        .collect();
+
+    matches.sort();
+    matches.dedup();
+    Ok(matches)
+}
+
+/// A walk of `dir` skipping what `.gitignore`, `.ignore` and git's exclude files exclude
+/// (unless `respect_ignore` is off), whether or not `dir` is inside a git repository.
+/// Hidden entries are listed; callers decide about those.
+pub fn walker(dir: &Path, respect_ignore: bool) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(dir);
+    builder
+        .standard_filters(respect_ignore)
+        .hidden(false)
+        .require_git(false);
+    builder
+}
+
+/// Splits `pattern` into the directory before its first wildcard and the glob after it
+/// (empty when the pattern has no wildcards at all)
+fn split_base(pattern: &str) -> (PathBuf, &str) {
+    let (mut base, mut rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (PathBuf::from("/"), rest),
+        None => (PathBuf::new(), pattern),
+    };
+
+    while let Some((component, tail)) = rest.split_once('/')
+        && !has_wildcards(component)
+    {
+        base.push(component);
+        rest = tail;
+    }
+    if !has_wildcards(rest) {
+        base.push(rest);
+        rest = "";
+    }
+
+    (base, rest)
+}
+
+fn has_wildcards(component: &str) -> bool {
+    component.contains(['*', '?', '[', '{'])
+}
+
+/// Matches a single path component against `*`, `?` and `[...]` wildcards
+pub fn matches_component(pattern: &str, name: &str) -> bool {
+    match Glob::new(pattern) {
+        Ok(glob) => glob.compile_matcher().is_match(name),
+        Err(_) => pattern == name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Expands `pattern` in a fresh directory holding empty `files`
+    fn expand_in(files: &[&str], pattern: &str) -> Vec<String> {
+        expand_ignoring(files, "", pattern, false)
+    }
+
+    /// Like `expand_in`, with a `.gitignore` of `rules` at the top of the directory
+    fn expand_ignoring(
+        files: &[&str],
+        rules: &str,
+        pattern: &str,
+        respect_ignore: bool,
+    ) -> Vec<String> {
+        static DIRS: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "scriptify-glob-{}-{}",
+            std::process::id(),
+            DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        std::fs::write(dir.join(".gitignore"), rules).unwrap();
+        let pattern = format!("{}/{pattern}", dir.display());
+        let matches = expand(&pattern, respect_ignore).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        matches
+            .iter()
+            .map(|path| path.strip_prefix(&dir).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn star_stays_within_a_component() {
+        let files = ["src/a.rs", "src/b.txt", "src/nested/c.rs", "src/.hidden.rs"];
+        assert_eq!(expand_in(&files, "src/*.rs"), ["src/a.rs"]);
+    }
+
+    #[test]
+    fn double_star_and_braces() {
+        let files = [
+            "examples/a.rs",
+            "examples/x/y/b.rs",
+            "bin/foo.rs",
+            "bin/bar.rs",
+            "bin/baz.rs",
+        ];
+        assert_eq!(
+            expand_in(&files, "examples/**/*.rs"),
+            ["examples/a.rs", "examples/x/y/b.rs"]
+        );
+        assert_eq!(
+            expand_in(&files, "bin/{foo,bar}.rs"),
+            ["bin/bar.rs", "bin/foo.rs"]
+        );
+    }
+
+    #[test]
+    fn gitignored_files_are_skipped_unless_disabled() {
+        let files = ["src/a.rs", "gen/b.rs", "src/c.generated.rs"];
+        let rules = "gen/\n*.generated.rs\n";
+        assert_eq!(
+            expand_ignoring(&files, rules, "**/*.rs", true),
+            ["src/a.rs"]
+        );
+        assert_eq!(
+            expand_ignoring(&files, rules, "**/*.rs", false),
+            ["gen/b.rs", "src/a.rs", "src/c.generated.rs"]
+        );
+    }
+
+    #[test]
+    fn components_match_like_globs() {
+        assert!(matches_component("gen*", "generated"));
+        assert!(matches_component("[a-c]pi", "api"));
+        assert!(!matches_component("[!a]pi", "api"));
+        assert!(!matches_component("a?", "a"));
+    }
+}
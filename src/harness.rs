@@ -0,0 +1,184 @@
+//! `--wrap-test-harness`: turns `#[test]` functions into plain functions and adds a
+//! `fn main()` that runs them, since `cargo test` doesn't work on scripts.
+
+use syn::visit_mut::VisitMut;
+use syn::{Attribute, Ident, Item, ItemFn, ReturnType, Visibility, parse_quote};
+
+use crate::comments::item_attrs;
+
+const RENAMED_MAIN: &str = "__scriptify_original_main";
+
+struct TestFn {
+    path: Vec<Ident>,
+    should_panic: bool,
+    ignored: bool,
+    returns_result: bool,
+}
+
+/// Rewrites `file` into a runnable test script, returning the number of tests found.
+/// A file without tests is left as it is.
+pub fn wrap_tests(file: &mut syn::File) -> usize {
+    let mut wrapped = file.clone();
+    let mut collector = TestCollector::default();
+    collector.visit_file_mut(&mut wrapped);
+    if collector.tests.is_empty() {
+        return 0;
+    }
+
+    for item in &mut wrapped.items {
+        if let Item::Fn(function) = item
+            && function.sig.ident == "main"
+        {
+            function.sig.ident = Ident::new(RENAMED_MAIN, function.sig.ident.span());
+            function.attrs.push(parse_quote!(#[allow(dead_code)]));
+        }
+    }
+
+    wrapped.items.push(Item::Fn(test_main(&collector.tests)));
+    *file = wrapped;
+    collector.tests.len()
+}
+
+fn test_main(tests: &[TestFn]) -> ItemFn {
+    let entries = tests.iter().map(|test| {
+        let path = &test.path;
+        let name = path
+            .iter()
+            .map(Ident::to_string)
+            .collect::<Vec<_>>()
+            .join("::");
+        let (should_panic, ignored) = (test.should_panic, test.ignored);
+        let call: syn::Expr = if test.returns_result {
+            parse_quote!(#(#path)::*().unwrap())
+        } else {
+            parse_quote!(#(#path)::*())
+        };
+        let entry: syn::Expr = parse_quote!((#name, #should_panic, #ignored, || { #call; }));
+        entry
+    });
+
+    parse_quote! {
+        fn main() {
+            let tests: &[(&str, bool, bool, fn())] = &[#(#entries),*];
+            let (mut passed, mut failed, mut ignored) = (0, 0, 0);
+
+            println!("running {} tests", tests.len());
+            for &(name, should_panic, ignore, test) in tests {
+                if ignore {
+                    println!("test {name} ... ignored");
+                    ignored += 1;
+                    continue;
+                }
+
+                let panicked = std::panic::catch_unwind(test).is_err();
+                if panicked == should_panic {
+                    println!("test {name} ... ok");
+                    passed += 1;
+                } else {
+                    println!("test {name} ... FAILED");
+                    failed += 1;
+                }
+            }
+
+            let result = if failed == 0 { "ok" } else { "FAILED" };
+            println!("\ntest result: {result}. {passed} passed; {failed} failed; {ignored} ignored");
+            if failed > 0 {
+                std::process::exit(101);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct TestCollector {
+    /// Inline modules enclosing the current item
+    mod_path: Vec<Ident>,
+    tests: Vec<TestFn>,
+}
+
+impl VisitMut for TestCollector {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        if let Some(attrs) = item_attrs(item) {
+            attrs.retain(|attr| !is_cfg_test(attr));
+        }
+
+        match item {
+            Item::Fn(function) if has_attr(&function.attrs, "test") => {
+                let should_panic = has_attr(&function.attrs, "should_panic");
+                let ignored = has_attr(&function.attrs, "ignore");
+                function.attrs.retain(|a| {
+                    !["test", "should_panic", "ignore"]
+                        .iter()
+                        .any(|name| a.path().is_ident(name))
+                });
+                make_crate_visible(&mut function.vis);
+
+                let mut path = self.mod_path.clone();
+                path.push(function.sig.ident.clone());
+                self.tests.push(TestFn {
+                    path,
+                    should_panic,
+                    ignored,
+                    returns_result: !matches!(function.sig.output, ReturnType::Default),
+                });
+            }
+            Item::Mod(module) => {
+                let found = self.tests.len();
+                self.mod_path.push(module.ident.clone());
+                syn::visit_mut::visit_item_mod_mut(self, module);
+                self.mod_path.pop();
+
+                if self.tests.len() > found {
+                    make_crate_visible(&mut module.vis);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_cfg_test(attr: &Attribute) -> bool {
+    attr.path().is_ident("cfg")
+        && attr
+            .parse_args::<Ident>()
+            .is_ok_and(|predicate| predicate == "test")
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|a| a.path().is_ident(name))
+}
+
+fn make_crate_visible(vis: &mut Visibility) {
+    if matches!(vis, Visibility::Inherited) {
+        *vis = parse_quote!(pub(crate));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_tests_into_main() {
+        let mut file: syn::File = syn::parse_str(
+            "fn main() {}\n\
+             #[cfg(test)]\n\
+             mod tests {\n    #[test]\n    fn adds() {}\n    #[test]\n    #[should_panic]\n    fn panics() { panic!() }\n}\n",
+        )
+        .unwrap();
+        assert_eq!(wrap_tests(&mut file), 2);
+
+        let code = prettyplease::unparse(&file);
+        assert!(code.contains(RENAMED_MAIN), "{code}");
+        assert!(code.contains("tests::panics();"), "{code}");
+        assert!(!code.contains("cfg(test)"), "{code}");
+    }
+
+    #[test]
+    fn file_without_tests_is_untouched() {
+        let source = "fn main() {}\n#[cfg(test)]\nmod helpers {\n    pub fn fixture() {}\n}\n";
+        let mut file: syn::File = syn::parse_str(source).unwrap();
+        assert_eq!(wrap_tests(&mut file), 0);
+        assert_eq!(file, syn::parse_str::<syn::File>(source).unwrap());
+    }
+}
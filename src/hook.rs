@@ -0,0 +1,61 @@
+//! `--hook`: pipes the generated output through an external command, whose stdout
+//! replaces it.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::{Result, ScriptifyError};
+
+/// Runs `command` through the shell with `input` on stdin and returns its stdout
+pub fn run_hook(command: &str, input: &str) -> Result<String> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(ScriptifyError::io(format!(
+            "failed to run hook `{command}`"
+        )))?;
+
+    // Written from a separate thread so a hook that streams its output can't deadlock
+    // against a full stdin pipe
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(ScriptifyError::io(format!(
+            "failed to run hook `{command}`"
+        )))?;
+    // A hook may exit without reading all of its input, which is not an error by itself
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(ScriptifyError::HookFailed {
+            command: command.to_string(),
+            code: output
+                .status
+                .code()
+                .and_then(|code| u8::try_from(code).ok())
+                .filter(|&code| code != 0)
+                .unwrap_or(1),
+        });
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|_| format!("hook `{command}` produced invalid UTF-8").into())
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
@@ -0,0 +1,214 @@
+//! `--indent` / `--tab-width`: rewrites the leading indentation of prettyplease's
+//! output, which is always four spaces per level. A cosmetic post-pass, not a
+//! formatter: only whitespace at the start of a line changes, and lines that begin
+//! inside a string literal or block comment are left alone.
+
+const PRETTYPLEASE_WIDTH: usize = 4;
+
+/// Re-indents each level of `code` as one tab, or as `width` spaces
+pub fn reindent(code: &str, tabs: bool, width: usize) -> String {
+    let mut reindented = String::with_capacity(code.len());
+    let mut state = State::Code;
+
+    for line in code.split_inclusive('\n') {
+        if state == State::Code {
+            let content = line.trim_start_matches(' ');
+            let spaces = line.len() - content.len();
+            let (levels, rest) = (spaces / PRETTYPLEASE_WIDTH, spaces % PRETTYPLEASE_WIDTH);
+            if tabs {
+                reindented.push_str(&"\t".repeat(levels));
+            } else {
+                reindented.push_str(&" ".repeat(levels * width));
+            }
+            reindented.push_str(&" ".repeat(rest));
+            reindented.push_str(content);
+        } else {
+            reindented.push_str(line);
+        }
+
+        state = state.after(line);
+    }
+
+    reindented
+}
+
+/// What the scanner is inside of at the end of a line
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Code,
+    Str,
+    /// A raw string closed by `"` and this many `#`s
+    RawStr(usize),
+    /// A block comment nested this deep
+    BlockComment(usize),
+}
+
+impl State {
+    fn after(mut self, line: &str) -> Self {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let next = chars.get(i + 1).copied();
+            match self {
+                Self::Code => match chars[i] {
+                    '/' if next == Some('/') => return self,
+                    '/' if next == Some('*') => {
+                        self = Self::BlockComment(1);
+                        i += 1;
+                    }
+                    '"' => self = Self::Str,
+                    'r' if starts_literal(&chars, i) => {
+                        let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+                        if chars.get(i + 1 + hashes) == Some(&'"') {
+                            self = Self::RawStr(hashes);
+                            i += 1 + hashes;
+                        }
+                    }
+                    '\'' => i += char_literal_len(&chars[i..]),
+                    _ => {}
+                },
+                Self::Str => match chars[i] {
+                    '\\' => i += 1,
+                    '"' => self = Self::Code,
+                    _ => {}
+                },
+                Self::RawStr(hashes) => {
+                    if chars[i] == '"'
+                        && chars[i + 1..].iter().take_while(|&&c| c == '#').count() >= hashes
+                    {
+                        self = Self::Code;
+                        i += hashes;
+                    }
+                }
+                Self::BlockComment(depth) => match (chars[i], next) {
+                    ('/', Some('*')) => {
+                        self = Self::BlockComment(depth + 1);
+                        i += 1;
+                    }
+                    ('*', Some('/')) => {
+                        self = match depth {
+                            1 => Self::Code,
+                            _ => Self::BlockComment(depth - 1),
+                        };
+                        i += 1;
+                    }
+                    _ => {}
+                },
+            }
+            i += 1;
+        }
+
+        self
+    }
+}
+
+/// Whether the `r` at `i` begins a raw string prefix (`r`, `br` or `cr`) rather than
+/// being part of an identifier
+fn starts_literal(chars: &[char], i: usize) -> bool {
+    let start = match i.checked_sub(1).map(|p| chars[p]) {
+        Some('b' | 'c') => i - 1,
+        _ => i,
+    };
+    start == 0 || !is_ident_char(chars[start - 1])
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Characters to skip past a char literal starting at `chars[0] == '\''`, minus the
+/// quote itself; 0 for a lifetime or label
+fn char_literal_len(chars: &[char]) -> usize {
+    match chars.get(1) {
+        Some('\\') => chars
+            .get(3..)
+            .and_then(|rest| rest.iter().position(|&c| c == '\''))
+            .map_or(0, |end| end + 3),
+        Some(_) if chars.get(2) == Some(&'\'') => 2,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that reindenting `code` with tabs changes exactly the lines marked `>`
+    /// (the marker itself is dropped); the others begin inside a literal or comment
+    fn assert_reindented(code: &str) {
+        let (source, expected): (String, String) = code
+            .split_inclusive('\n')
+            .map(|line| match line.strip_prefix('>') {
+                Some(line) => {
+                    let content = line.trim_start_matches(' ');
+                    let levels = (line.len() - content.len()) / PRETTYPLEASE_WIDTH;
+                    (
+                        line.to_string(),
+                        format!("{}{content}", "\t".repeat(levels)),
+                    )
+                }
+                None => (line.to_string(), line.to_string()),
+            })
+            .unzip();
+        assert_eq!(reindent(&source, true, 4), expected);
+    }
+
+    #[test]
+    fn levels_become_tabs_or_spaces() {
+        let code = "fn main() {\n    if x {\n        y();\n    }\n}\n";
+        assert_eq!(
+            reindent(code, true, 4),
+            "fn main() {\n\tif x {\n\t\ty();\n\t}\n}\n"
+        );
+        assert_eq!(
+            reindent(code, false, 2),
+            "fn main() {\n  if x {\n    y();\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn raw_strings_end_at_matching_hashes() {
+        assert_reindented(
+            ">fn f() {\n\
+             >    let s = r##\"a \"# not the end\n    \"## ;\n\
+             >    let t = br##\"x\"#\n    y\"##;\n\
+             >    let r#type = 1;\n\
+             >}\n",
+        );
+    }
+
+    #[test]
+    fn lifetimes_are_not_char_literals() {
+        assert_reindented(
+            ">fn f<'a>(s: &'a str) -> [char; 3] {\n\
+             >    'outer: loop {\n\
+             >        break 'outer;\n\
+             >    }\n\
+             >    ['\"', '\\'', b'\"' as char]\n\
+             >}\n\
+             >const S: &str = \"\n    inside\";\n",
+        );
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_reindented(
+            ">fn f() {\n\
+             >    /* outer /* inner */\n    still a comment */\n\
+             >    g();\n\
+             >}\n",
+        );
+    }
+
+    #[test]
+    fn escaped_quotes_stay_in_the_string() {
+        assert_reindented(
+            ">fn f() {\n\
+             >    let s = \"say \\\"hi\\\n    \\\" // not a comment\n    end\";\n\
+             >    let t = \"\\\\\";\n\
+             >    g();\n\
+             >}\n",
+        );
+    }
+}
@@ -0,0 +1,606 @@
+//! Recursively replaces `mod foo;` declarations with the contents of the files they
+//! refer to, following rustc's lookup rules (`foo.rs`, `foo/mod.rs`, `#[path]`);
+//! like rustc, having both `foo.rs` and `foo/mod.rs` is an error.
+//! Modules missing from their default location are then looked up, at the same
+//! path relative to the crate root, in each include directory in order. Modules
+//! matching an exclusion pattern are dropped along with their declaration; with a
+//! module filter, modules that can't lead to a match keep their declaration.
+//! With a banner template, each inlined module is headed by a comment naming its origin.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use syn::visit_mut::VisitMut;
+use syn::{Expr, ExprLit, Item, ItemMod, Lit, Meta};
+
+use crate::encoding::{self, Encoding};
+use crate::glob::matches_component;
+use crate::progress::Progress;
+use crate::{Result, ScriptifyError, assets, cache, comments, diagnostics};
+
+/// A module declaration that could not be inlined and was left as `mod foo;`
+pub struct InlineError {
+    pub src_path: PathBuf,
+    pub module_name: String,
+    pub line: usize,
+    pub column: usize,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for InlineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "module `{}` was not inlined from '{}': {} (declared at {}:{}:{})",
+            self.module_name,
+            self.path.display(),
+            self.reason,
+            self.src_path.display(),
+            self.line,
+            self.column
+        )
+    }
+}
+
+/// How modules are looked up and what is kept while inlining them
+#[derive(Default)]
+pub struct InlineOptions<'a> {
+    /// Fallback roots, searched in order when a module isn't found in its default location
+    pub include_dirs: &'a [PathBuf],
+    /// Carry `//` comments above items through to the output (see `comments`)
+    pub preserve_comments: bool,
+    /// Module path globs (e.g. `generated::*`) whose modules are left out entirely
+    pub exclude_modules: &'a [String],
+    /// Module path globs limiting which modules are inlined (all when empty)
+    pub module_filter: &'a [String],
+    /// Show a module counter on stderr when inlining takes a while
+    pub progress: bool,
+    /// Replace `include_str!`/`include_bytes!` of files with their contents
+    pub embed_assets: bool,
+    /// Encoding every source file is decoded from
+    pub encoding: Encoding,
+    /// Template of the `//` banner put above every inlined module (see `module_banner`)
+    pub module_banner: Option<&'a str>,
+    /// Name banner files relative to the crate root's directory, with `/` separators, so the
+    /// banner doesn't depend on where the crate is or on the platform
+    pub relative_banner_paths: bool,
+}
+
+/// A module file that was read and inlined
+pub struct InlinedFile {
+    /// Module names from the crate root down to the module
+    pub module_path: Vec<String>,
+    pub path: PathBuf,
+}
+
+impl InlineError {
+    /// Where the module was declared
+    pub fn location(&self) -> diagnostics::Location {
+        diagnostics::Location {
+            file: self.src_path.clone(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+/// Parses `path` and inlines every module it declares
+pub fn inline_file(path: &Path, options: &InlineOptions) -> Result<(syn::File, Vec<InlineError>)> {
+    let source = encoding::read_to_string(path, options.encoding)?;
+
+    inline_source(&source, path, options)
+}
+
+/// Parses `source` as if it were the file at `path` and inlines every module it declares
+pub fn inline_source(
+    source: &str,
+    path: &Path,
+    options: &InlineOptions,
+) -> Result<(syn::File, Vec<InlineError>)> {
+    let (file, errors, _) = inline_tracked(source, path, options)?;
+    Ok((file, errors))
+}
+
+/// Resolves the modules of `source` (the file at `path`) as `inline_source` does and
+/// returns the files that were inlined, in declaration order, instead of the crate
+pub fn resolve_files(
+    source: &str,
+    path: &Path,
+    options: &InlineOptions,
+) -> Result<(Vec<InlinedFile>, Vec<InlineError>)> {
+    let (_, errors, files) = inline_tracked(source, path, options)?;
+    Ok((files, errors))
+}
+
+fn inline_tracked(
+    source: &str,
+    path: &Path,
+    options: &InlineOptions,
+) -> Result<(syn::File, Vec<InlineError>, Vec<InlinedFile>)> {
+    cache::track(path);
+    let mut file = syn::parse_file(source).map_err(|e| parse_error(path, &e))?;
+    let mut errors = Vec::new();
+    let roots = SearchRoots {
+        crate_dir: path.parent().unwrap_or(Path::new("")),
+        options,
+        excluded: Cell::new(0),
+        files: RefCell::new(Vec::new()),
+        progress: Progress::new(options.progress),
+    };
+    if options.preserve_comments {
+        comments::attach(source, &mut file);
+    }
+    roots.embed_assets(&mut file, path);
+
+    ModuleVisitor::new(path, true, Vec::new(), &roots, &mut errors).visit_file_mut(&mut file);
+    roots.progress.clear();
+
+    match roots.excluded.get() {
+        0 if !options.exclude_modules.is_empty() => {
+            diagnostics::warn("no modules matched the exclusion patterns")
+        }
+        0 => {}
+        count => diagnostics::warn(format!("excluded {count} module(s) in total")),
+    }
+
+    Ok((file, errors, roots.files.into_inner()))
+}
+
+pub fn parse_error(path: &Path, error: &syn::Error) -> ScriptifyError {
+    let start = error.span().start();
+    ScriptifyError::ParseError {
+        file: path.to_path_buf(),
+        line: start.line,
+        column: start.column + 1,
+        message: error.to_string(),
+    }
+}
+
+#[derive(Clone)]
+enum ModSegment {
+    Ident(String),
+    Path(PathBuf),
+}
+
+impl From<&ItemMod> for ModSegment {
+    fn from(item: &ItemMod) -> Self {
+        for attr in &item.attrs {
+            if let Meta::NameValue(name_value) = &attr.meta
+                && name_value.path.is_ident("path")
+                && let Expr::Lit(ExprLit {
+                    lit: Lit::Str(path),
+                    ..
+                }) = &name_value.value
+            {
+                return ModSegment::Path(path.value().into());
+            }
+        }
+
+        ModSegment::Ident(item.ident.to_string())
+    }
+}
+
+/// Directories module files are resolved against
+struct SearchRoots<'a> {
+    /// Directory of the crate root file
+    crate_dir: &'a Path,
+    options: &'a InlineOptions<'a>,
+    /// Number of modules dropped by exclusion patterns so far
+    excluded: Cell<usize>,
+    /// Module files loaded so far, parents before their submodules
+    files: RefCell<Vec<InlinedFile>>,
+    progress: Progress,
+}
+
+impl SearchRoots<'_> {
+    /// `candidates` re-rooted under each include directory, in search order
+    fn fallbacks(&self, candidates: &[PathBuf]) -> Vec<PathBuf> {
+        let relative: Vec<&Path> = candidates
+            .iter()
+            .filter_map(|candidate| {
+                std::iter::once(self.crate_dir)
+                    .chain(self.options.include_dirs.iter().map(PathBuf::as_path))
+                    .find_map(|root| candidate.strip_prefix(root).ok())
+            })
+            .collect();
+
+        self.options
+            .include_dirs
+            .iter()
+            .flat_map(|dir| relative.iter().map(move |path| dir.join(path)))
+            .collect()
+    }
+
+    /// Embeds the assets of one file before its submodules are inlined, while `path` is
+    /// still known to be the file the include paths are relative to
+    fn embed_assets(&self, file: &mut syn::File, path: &Path) {
+        if !self.options.embed_assets {
+            return;
+        }
+        for warning in assets::embed_assets(file, path) {
+            self.progress.clear();
+            diagnostics::warn(warning);
+        }
+    }
+
+    /// How a banner names `path`: as found, or relative to the crate root's directory
+    fn banner_path(&self, path: &Path) -> String {
+        if !self.options.relative_banner_paths {
+            return path.display().to_string();
+        }
+        let relative = path.strip_prefix(self.crate_dir).unwrap_or(path);
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn is_excluded(&self, module_path: &[String]) -> bool {
+        self.options
+            .exclude_modules
+            .iter()
+            .any(|pattern| module_path_matches(&pattern_segments(pattern), module_path))
+    }
+
+    /// Whether the module may be inlined under the filter: it matches a pattern itself or
+    /// a module below it might
+    fn is_allowed(&self, module_path: &[String]) -> bool {
+        self.options.module_filter.is_empty()
+            || self.options.module_filter.iter().any(|pattern| {
+                let pattern = pattern_segments(pattern);
+                module_path_matches(&pattern, module_path) || leads_to_match(&pattern, module_path)
+            })
+    }
+}
+
+fn pattern_segments(pattern: &str) -> Vec<&str> {
+    pattern.trim_start_matches("crate::").split("::").collect()
+}
+
+/// Whether a module below `path` could match `pattern`
+fn leads_to_match(pattern: &[&str], path: &[String]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (Some((&"**", _)), _) => true,
+        (Some(_), None) => true,
+        (Some((segment, rest)), Some((name, path))) => {
+            matches_component(segment, name) && leads_to_match(rest, path)
+        }
+        (None, _) => false,
+    }
+}
+
+/// Fills in the `{module}` and `{file}` placeholders of a `--group-by-module` banner
+fn module_banner(template: &str, module_path: &[String], file: &str) -> String {
+    template
+        .replace("{module}", &module_path.join("::"))
+        .replace("{file}", file)
+}
+
+/// Matches module path segments against `*`/`?`/`[...]` per segment and `**` across segments
+fn module_path_matches(pattern: &[&str], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=path.len()).any(|skip| module_path_matches(rest, &path[skip..]))
+        }
+        Some((segment, rest)) => path.split_first().is_some_and(|(name, path)| {
+            matches_component(segment, name) && module_path_matches(rest, path)
+        }),
+    }
+}
+
+struct ModuleVisitor<'a> {
+    /// The file currently being visited
+    path: &'a Path,
+    /// Whether `path` owns its directory, like the crate root, a `mod.rs` or a file loaded
+    /// through `#[path]`: its modules resolve next to it rather than below it
+    owns_dir: bool,
+    roots: &'a SearchRoots<'a>,
+    /// Inline `mod` blocks enclosing the current position within `path`
+    mod_context: Vec<ModSegment>,
+    /// Module names from the crate root down to the current position
+    module_path: Vec<String>,
+    errors: &'a mut Vec<InlineError>,
+}
+
+impl<'a> ModuleVisitor<'a> {
+    fn new(
+        path: &'a Path,
+        owns_dir: bool,
+        module_path: Vec<String>,
+        roots: &'a SearchRoots<'a>,
+        errors: &'a mut Vec<InlineError>,
+    ) -> Self {
+        Self {
+            path,
+            owns_dir,
+            roots,
+            mod_context: Vec::new(),
+            module_path,
+            errors,
+        }
+    }
+
+    /// Drops the modules in `items` that match an exclusion pattern
+    fn remove_excluded(&self, items: &mut Vec<Item>) {
+        if self.roots.options.exclude_modules.is_empty() {
+            return;
+        }
+
+        items.retain(|item| {
+            let Item::Mod(module) = item else {
+                return true;
+            };
+            let mut module_path = self.module_path.clone();
+            module_path.push(module.ident.to_string());
+            if !self.roots.is_excluded(&module_path) {
+                return true;
+            }
+
+            self.roots.progress.clear();
+            diagnostics::warn(format!("excluded module `{}`", module_path.join("::")));
+            self.roots.excluded.set(self.roots.excluded.get() + 1);
+            false
+        });
+    }
+
+    /// Candidate files for the innermost module of `mod_context`, in lookup order
+    fn candidates(&self) -> Vec<PathBuf> {
+        let mut dir = self.path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let is_mod_file = self.path.file_name().is_some_and(|name| name == "mod.rs");
+        // Like rustc, a `#[path]` outside any inline `mod {}` block is relative to the
+        // declaring file's directory even when that file doesn't own it
+        let top_level_path = matches!(self.mod_context.as_slice(), [ModSegment::Path(_)]);
+        if !self.owns_dir && !is_mod_file && !top_level_path {
+            dir.push(self.path.file_stem().unwrap_or_default());
+        }
+
+        for segment in &self.mod_context {
+            match segment {
+                ModSegment::Ident(name) => dir.push(name),
+                ModSegment::Path(path) => dir.push(path),
+            }
+        }
+
+        match self.mod_context.last() {
+            Some(ModSegment::Ident(_)) => vec![dir.with_extension("rs"), dir.join("mod.rs")],
+            _ => vec![dir],
+        }
+    }
+
+    fn declared_at(&self, item: &ItemMod) -> diagnostics::Location {
+        let start = item.ident.span().start();
+        diagnostics::Location {
+            file: self.path.to_path_buf(),
+            line: start.line,
+            column: start.column + 1,
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> std::result::Result<syn::File, String> {
+        self.roots.progress.tick();
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        cache::track(path);
+        let source = encoding::decode(bytes, self.roots.options.encoding)
+            .map_err(|reason| format!("not valid {}: {reason}", self.roots.options.encoding))?;
+        let mut file = syn::parse_file(&source).map_err(|e| e.to_string())?;
+        self.roots.files.borrow_mut().push(InlinedFile {
+            module_path: self.module_path.clone(),
+            path: path.to_path_buf(),
+        });
+        if self.roots.options.preserve_comments {
+            comments::attach(&source, &mut file);
+        }
+        self.roots.embed_assets(&mut file, path);
+
+        let via_path = matches!(self.mod_context.last(), Some(ModSegment::Path(_)));
+        ModuleVisitor::new(
+            path,
+            via_path,
+            self.module_path.clone(),
+            self.roots,
+            self.errors,
+        )
+        .visit_file_mut(&mut file);
+
+        Ok(file)
+    }
+}
+
+impl VisitMut for ModuleVisitor<'_> {
+    fn visit_file_mut(&mut self, file: &mut syn::File) {
+        self.remove_excluded(&mut file.items);
+        syn::visit_mut::visit_file_mut(self, file);
+    }
+
+    fn visit_item_mod_mut(&mut self, item: &mut ItemMod) {
+        self.mod_context.push(ModSegment::from(&*item));
+        self.module_path.push(item.ident.to_string());
+
+        if let Some((_, items)) = &mut item.content {
+            self.remove_excluded(items);
+            for item in items {
+                self.visit_item_mut(item);
+            }
+        } else if !self.roots.is_allowed(&self.module_path) {
+            self.roots.progress.clear();
+            diagnostics::warn_at(
+                Some(self.declared_at(item)),
+                format!(
+                    "module `{}` left as a declaration: it doesn't match --module-filter",
+                    self.module_path.join("::")
+                ),
+            );
+        } else {
+            let candidates = self.candidates();
+            let fallbacks = self.roots.fallbacks(&candidates);
+            // Every search root contributes the same candidates, in the same order
+            let existing = std::iter::once(candidates.as_slice())
+                .chain(fallbacks.chunks(candidates.len()))
+                .map(|group| {
+                    group
+                        .iter()
+                        .filter(|path| path.exists())
+                        .collect::<Vec<_>>()
+                })
+                .find(|found| !found.is_empty())
+                .unwrap_or_default();
+
+            let (chosen, loaded) = match existing.as_slice() {
+                [found] => ((*found).clone(), self.load(found)),
+                [first, second, ..] => (
+                    (*first).clone(),
+                    Err(format!(
+                        "ambiguous, both '{}' and '{}' exist",
+                        first.display(),
+                        second.display()
+                    )),
+                ),
+                [] => {
+                    let chosen = candidates
+                        .last()
+                        .expect("candidates are never empty")
+                        .clone();
+                    let loaded = if fallbacks.is_empty() {
+                        self.load(&chosen)
+                    } else {
+                        Err("not found next to its parent or in any include directory".to_string())
+                    };
+                    (chosen, loaded)
+                }
+            };
+
+            match loaded {
+                Ok(file) => {
+                    if let Some(template) = self.roots.options.module_banner {
+                        let file = self.roots.banner_path(&chosen);
+                        let banner = module_banner(template, &self.module_path, &file);
+                        item.attrs
+                            .insert(0, comments::marker(&format!(" {banner}")));
+                    }
+                    item.attrs.extend(file.attrs);
+                    item.content = Some((Default::default(), file.items));
+                    item.semi = None;
+                }
+                Err(reason) => {
+                    let start = item.ident.span().start();
+                    self.errors.push(InlineError {
+                        src_path: self.path.to_path_buf(),
+                        module_name: item.ident.to_string(),
+                        line: start.line,
+                        column: start.column + 1,
+                        path: chosen,
+                        reason,
+                    });
+                }
+            }
+        }
+
+        self.mod_context.pop();
+        self.module_path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Crate root of a fixture under `tests/fixtures`
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+            .join("src/main.rs")
+    }
+
+    /// The files inlined for the crate at `root`, relative to its directory
+    fn inlined_files(root: &Path) -> Vec<String> {
+        let (files, errors) = resolve(root);
+        assert!(errors.is_empty(), "{}", errors[0]);
+        files
+    }
+
+    /// Like `inlined_files`, but also returns the modules that couldn't be inlined
+    fn resolve(root: &Path) -> (Vec<String>, Vec<InlineError>) {
+        resolve_with(root, &InlineOptions::default())
+    }
+
+    fn resolve_with(root: &Path, options: &InlineOptions) -> (Vec<String>, Vec<InlineError>) {
+        let source = std::fs::read_to_string(root).unwrap();
+        let (files, errors) = resolve_files(&source, root, options).unwrap();
+        let dir = root.parent().unwrap();
+        let files = files
+            .iter()
+            .map(|file| file.path.strip_prefix(dir).unwrap().display().to_string())
+            .collect();
+        (files, errors)
+    }
+
+    #[test]
+    fn path_attributes_resolve_like_rustc() {
+        assert_eq!(
+            inlined_files(&fixture("path_attr")),
+            ["net.rs", "wire.rs", "codec.rs", "net/inner/deep.rs"]
+        );
+    }
+
+    #[test]
+    fn both_module_layouts_resolve_and_ambiguity_is_reported() {
+        let (files, errors) = resolve(&fixture("mod_layout"));
+        assert_eq!(
+            files,
+            [
+                "legacy/mod.rs",
+                "legacy/nested.rs",
+                "modern.rs",
+                "modern/nested.rs"
+            ]
+        );
+
+        let [error] = errors.as_slice() else {
+            panic!("expected one error, got {}", errors.len());
+        };
+        assert_eq!(error.module_name, "both");
+        assert!(error.reason.starts_with("ambiguous"), "{}", error.reason);
+    }
+
+    #[test]
+    fn module_filter_inlines_only_matching_modules() {
+        let filter = ["api::*".to_string()];
+        let options = InlineOptions {
+            module_filter: &filter,
+            ..InlineOptions::default()
+        };
+        let (files, errors) = resolve_with(&fixture("module_filter"), &options);
+        // `api` leads to the matches, `api::v1::deep` is below them and `db` is outside
+        assert_eq!(files, ["api.rs", "api/users.rs", "api/v1.rs"]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn module_path_wildcards() {
+        let matches = |pattern: &str, path: &str| {
+            let pattern: Vec<&str> = pattern.split("::").collect();
+            let path: Vec<String> = path.split("::").map(str::to_string).collect();
+            module_path_matches(&pattern, &path)
+        };
+
+        // `*` stands for exactly one segment
+        assert!(matches("api::*", "api::users"));
+        assert!(matches("*::users", "api::users"));
+        assert!(matches("api::v*", "api::v1"));
+        assert!(!matches("api::*", "api"));
+        assert!(!matches("api::*", "api::v1::deep"));
+        assert!(!matches("api::*", "db::users"));
+
+        // `**` stands for any number of segments, including none
+        assert!(matches("api::**", "api"));
+        assert!(matches("api::**", "api::v1::deep"));
+        assert!(matches("**::deep", "api::v1::deep"));
+        assert!(matches("api::**::deep", "api::deep"));
+        assert!(!matches("api::**", "db"));
+        assert!(!matches("**::deep", "api::v1"));
+    }
+}
@@ -0,0 +1,59 @@
+//! `--max-line-length` / `--wrap`: reports lines of the output longer than a limit, and
+//! optionally splits over-long `use` lists one item per line. Reflowing arbitrary Rust
+//! is out of scope, so every other long line is only reported.
+
+/// 1-based line numbers and widths of the lines of `text` wider than `limit`, counting
+/// a tab as `tab_width` columns
+pub fn long_lines(text: &str, limit: usize, tab_width: usize) -> Vec<(usize, usize)> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let width = line
+                .chars()
+                .map(|c| if c == '\t' { tab_width } else { 1 })
+                .sum();
+            (width > limit).then_some((index + 1, width))
+        })
+        .collect()
+}
+
+/// Splits each single-line `use a::{b, c};` longer than `limit` into one item per line,
+/// indented by one more `indent`. Lists with nested groups are left alone.
+pub fn wrap_use_lists(code: &str, limit: usize, indent: &str) -> String {
+    let mut wrapped = String::with_capacity(code.len());
+    for line in code.split_inclusive('\n') {
+        let content = line.trim_end_matches('\n');
+        match split_use_list(content).filter(|_| content.chars().count() > limit) {
+            Some((head, items)) => {
+                let leading = &content[..content.len() - content.trim_start().len()];
+                wrapped.push_str(head);
+                wrapped.push_str("{\n");
+                for item in items {
+                    wrapped.push_str(&format!("{leading}{indent}{item},\n"));
+                }
+                wrapped.push_str(&format!("{leading}}};\n"));
+            }
+            None => wrapped.push_str(line),
+        }
+    }
+    wrapped
+}
+
+/// Splits `use a::b::{c, d};` into `use a::b::` and its items
+fn split_use_list(line: &str) -> Option<(&str, Vec<&str>)> {
+    let open = line.find('{')?;
+    let (head, list) = (&line[..open], line[open + 1..].strip_suffix("};")?);
+    let keyword = head
+        .split_whitespace()
+        .find(|word| !word.starts_with("pub"));
+    if keyword != Some("use") || list.contains(['{', '}', '"', '/']) {
+        return None;
+    }
+
+    let items: Vec<&str> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect();
+    (items.len() > 1).then_some((head, items))
+}
@@ -0,0 +1,142 @@
+//! `--lines`/`--context`: cuts the output down to a range of lines, optionally widened by
+//! a few lines on each side like `grep -C`. On highlighted output the context lines are
+//! dimmed so the selected range stands out.
+
+use crate::{Result, ScriptifyError};
+
+/// A 1-based, inclusive `START:END` line range
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+pub fn parse_range(arg: &str) -> std::result::Result<LineRange, String> {
+    let (start, end) = arg
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, got '{arg}'"))?;
+    let number = |text: &str| {
+        text.trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| format!("invalid line number '{text}': lines are counted from 1"))
+    };
+    let range = LineRange {
+        start: number(start)?,
+        end: number(end)?,
+    };
+    if range.end < range.start {
+        return Err(format!("the range {arg} ends before it starts"));
+    }
+    Ok(range)
+}
+
+/// The lines of `text` in `range`, with up to `context` more on each side. `highlighted`
+/// is `text` as highlighted for the terminal, if it is: the selected lines are then taken
+/// from it, and the context lines are shown plain and dimmed.
+pub fn select(
+    text: &str,
+    highlighted: Option<&str>,
+    range: LineRange,
+    context: usize,
+) -> Result<String> {
+    let plain: Vec<&str> = text.lines().collect();
+    if range.start > plain.len() {
+        return Err(ScriptifyError::Usage(format!(
+            "--lines {}:{} starts past the end of the output ({} lines)",
+            range.start,
+            range.end,
+            plain.len()
+        )));
+    }
+
+    // Highlighting that doesn't keep the line structure can't be cut up line by line
+    let highlighted = highlighted
+        .map(ansi_lines)
+        .filter(|lines| lines.len() == plain.len());
+
+    let focus = range.start - 1..range.end.min(plain.len());
+    let shown = focus.start.saturating_sub(context)..(focus.end + context).min(plain.len());
+
+    let mut selected = String::new();
+    for i in shown {
+        match &highlighted {
+            Some(lines) if focus.contains(&i) => selected.push_str(&lines[i]),
+            Some(_) => selected.push_str(&format!("\x1b[2m{}\x1b[0m", plain[i])),
+            None => selected.push_str(plain[i]),
+        }
+        selected.push('\n');
+    }
+    Ok(selected)
+}
+
+/// Splits ANSI-highlighted text into lines that each stand on their own: a style still
+/// open at the end of a line is reset there and reopened at the start of the next one
+pub fn ansi_lines(text: &str) -> Vec<String> {
+    let mut active = String::new();
+    text.lines()
+        .map(|line| {
+            let mut rendered = active.clone();
+            rendered.push_str(line);
+
+            let mut rest = line;
+            while let Some(start) = rest.find("\x1b[") {
+                let Some(end) = rest[start..].find('m') else {
+                    break;
+                };
+                let sequence = &rest[start..start + end + 1];
+                if sequence == "\x1b[0m" || sequence == "\x1b[m" {
+                    active.clear();
+                } else {
+                    active.push_str(sequence);
+                }
+                rest = &rest[start + end + 1..];
+            }
+
+            if !active.is_empty() {
+                rendered.push_str("\x1b[0m");
+            }
+            rendered
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("2:4"), Ok(LineRange { start: 2, end: 4 }));
+        assert!(parse_range("0:4").is_err());
+        assert!(parse_range("4:2").is_err());
+        assert!(parse_range("4").is_err());
+    }
+
+    #[test]
+    fn context_is_clamped_to_the_text() {
+        let range = LineRange { start: 2, end: 3 };
+        assert_eq!(select(TEXT, None, range, 0).unwrap(), "two\nthree\n");
+        assert_eq!(
+            select(TEXT, None, range, 1).unwrap(),
+            "one\ntwo\nthree\nfour\n"
+        );
+        assert_eq!(select(TEXT, None, range, 10).unwrap(), TEXT);
+        let past_end = LineRange { start: 4, end: 9 };
+        assert_eq!(select(TEXT, None, past_end, 0).unwrap(), "four\nfive\n");
+        assert!(select(TEXT, None, LineRange { start: 6, end: 9 }, 0).is_err());
+    }
+
+    #[test]
+    fn context_lines_are_dimmed_when_highlighted() {
+        let highlighted = "\x1b[31mone\ntwo\x1b[0m\nthree\nfour\nfive\n";
+        let range = LineRange { start: 2, end: 2 };
+        assert_eq!(
+            select(TEXT, Some(highlighted), range, 1).unwrap(),
+            "\x1b[2mone\x1b[0m\n\x1b[31mtwo\x1b[0m\n\x1b[2mthree\x1b[0m\n"
+        );
+    }
+}
@@ -1,32 +1,374 @@
 use arborium::{AnsiHighlighter, theme::builtin};
-use clap::Parser;
+use base64::Engine;
+use clap::{Parser, Subcommand, ValueEnum};
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use syn_inline_mod::InlinerBuilder;
+use std::process::ExitCode;
+
+mod alphabetize;
+mod assets;
+mod cache;
+mod comments;
+mod contrast;
+mod deps;
+mod diagnostics;
+mod diff;
+mod empty_mods;
+mod encoding;
+mod error;
+mod extract;
+mod fetch;
+mod fn_size;
+mod formatter;
+mod glob;
+mod harness;
+mod hook;
+mod indent;
+mod inline;
+mod line_length;
+mod lines;
+mod measure;
+mod minify;
+mod path_deps;
+mod picker;
+mod progress;
+mod reexports;
+mod run_args;
+mod self_test;
+mod shebang;
+mod strip_derive;
+mod strip_private;
+mod template;
+mod timing;
+mod truncate_strings;
+mod unsafe_scan;
+mod workspace;
+mod wrapper;
 
 const DEFAULT_SHEBANG: &str = "#!/usr/bin/env -S RUSTC_BOOTSTRAP=1 RUSTFLAGS=-Coverflow-checks cargo run -qZscript --release --manifest-path";
+const NIGHTLY_SHEBANG: &str = "#!/usr/bin/env -S cargo +nightly -Zscript";
+const RUST_SCRIPT_SHEBANG: &str = "#!/usr/bin/env rust-script";
+
+const STDIN_INPUT: &str = "-";
+const STDIN_LABEL: &str = "<stdin>";
+
+const FRONTMATTER_FENCE: &str = "---";
+const DEFAULT_SECTION: &str = "cargo";
+const FRONTMATTER_CLOSE: &str = "---\n";
+const DOC_MANIFEST_OPEN: &str = "//! ```cargo\n";
+const DOC_MANIFEST_CLOSE: &str = "//! ```\n";
+/// Shown by --list-themes without --theme-preview-code
+const THEME_PREVIEW: &str = "/// Greets everyone listed on the command line
+fn main() {
+    let names: Vec<String> = std::env::args().skip(1).collect();
+    for (i, name) in names.iter().enumerate() {
+        println!(\"{i}: Hello, {name}!\"); // 0-based
+    }
+}";
+
+/// Lines of --dump-ast output shown on a terminal
+const DUMP_AST_MAX_LINES: usize = 2000;
+
+const DEFAULT_MODULE_BANNER: &str = "==== module: {module} (from {file}) ====";
+
+use encoding::Encoding;
+use error::ScriptifyError;
+use formatter::Formatter;
+use timing::Phase;
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+type Result<T> = std::result::Result<T, ScriptifyError>;
 
 /// Inline Rust modules with optional syntax highlighting and cargo-script support
-#[derive(Parser)]
+#[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    /// Input Rust source file or directory (use "." for current directory)
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input Rust source file or directory (use "." for current directory, "-" for stdin),
+    /// or an http(s) URL with --allow-net
     input: Option<PathBuf>,
 
+    /// Allow fetching the input from an http(s) URL (submodules are not resolved)
+    #[arg(long)]
+    allow_net: bool,
+
+    /// Path stdin is treated as for module resolution, manifest discovery and messages
+    #[arg(long)]
+    stdin_filename: Option<PathBuf>,
+
+    /// Encoding of the input and its module files, converted to UTF-8 before parsing
+    #[arg(long, value_enum, default_value_t = Encoding::Utf8)]
+    input_encoding: Encoding,
+
+    /// Glob selecting multiple inputs, supporting `**` and `{a,b}` (requires --output-dir)
+    #[arg(long, conflicts_with = "input", requires = "output_dir")]
+    input_glob: Option<String>,
+
+    /// Binary target to inline when the input is a crate directory (explicit or under src/bin/)
+    #[arg(long)]
+    bin: Option<String>,
+
+    /// Emit only the given module subtree (e.g. `net::http`) as a standalone file
+    #[arg(long, value_name = "PATH")]
+    only_module: Option<String>,
+
+    /// Remove top-level items that aren't `pub` or `pub(crate)`, except those a retained
+    /// item uses
+    #[arg(long)]
+    strip_private: bool,
+
+    /// Remove this trait from `#[derive(...)]` lists, e.g. `Serialize` or `serde::Serialize`,
+    /// along with imports of it nothing else uses (repeatable)
+    #[arg(long, value_name = "TRAIT")]
+    strip_derive: Vec<String>,
+
+    /// Remove inlined modules that end up without any items (e.g. files with only comments)
+    #[arg(long)]
+    drop_empty_mods: bool,
+
+    /// Leave out modules matching this path glob, e.g. `generated::*` (repeatable;
+    /// `*` matches one path segment, `**` any number)
+    #[arg(long, value_name = "PATTERN")]
+    exclude_module: Vec<String>,
+
+    /// Inline only modules matching this path glob, e.g. `api::**`, plus the modules on the
+    /// way to them; others stay `mod x;` declarations (repeatable)
+    #[arg(long, value_name = "PATTERN", conflicts_with = "exclude_module")]
+    module_filter: Vec<String>,
+
+    /// Fail, listing them, if any `mod x;` declaration is left in the output (e.g. a missing,
+    /// excluded or filtered module), so the script is guaranteed to be self-contained
+    #[arg(long)]
+    assert_single_file: bool,
+
+    /// Replace `include_str!`/`include_bytes!` of files with literals holding their contents
+    #[arg(long)]
+    embed_assets: bool,
+
+    /// Inline the library of each `path` dependency the code uses as a module, moving its
+    /// dependencies into the manifest (one level deep; needs -m or -z)
+    #[arg(long)]
+    inline_path_deps: bool,
+
+    /// Extra directory to look for module files in when they aren't next to their parent
+    /// (repeatable; searched in order after the default location)
+    #[arg(short = 'I', long, value_name = "DIR")]
+    include_dir: Vec<PathBuf>,
+
+    /// Entry point file to inline when the input is a directory, bypassing detection
+    /// (relative to the directory unless absolute)
+    #[arg(long, conflicts_with = "bin")]
+    entry: Option<PathBuf>,
+
+    /// Don't skip files excluded by .gitignore when scanning src/bin/ or expanding --input-glob
+    #[arg(long)]
+    no_ignore: bool,
+
     /// Output file (defaults to stdout)
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Enable syntax highlighting with specified theme
+    /// Leave --output alone when it is up to date: when none of its source files changed
+    /// and the options, manifest and shebang are the same as when it was written
+    /// (recorded in `<OUTPUT>.scriptify-cache`)
+    #[arg(long, requires = "output", conflicts_with_all = ["diff", "measure_compile", "verify_run"])]
+    cache: bool,
+
+    /// Write each input to `<DIR>/<stem>.rs` instead of a single output
+    #[arg(long, visible_alias = "out-dir", conflicts_with_all = ["output", "theme", "compress", "pick_theme"])]
+    output_dir: Option<PathBuf>,
+
+    /// Name --output-dir files after the crate's `package.name` for crate directory inputs
+    #[arg(long, requires = "output_dir")]
+    out_stem_from_manifest: bool,
+
+    /// Write a cargo project (`src/main.rs` and `Cargo.toml`) to --out-dir instead of a script
+    #[arg(long, requires = "output_dir", conflicts_with_all = ["input_glob", "diff", "no_frontmatter"])]
+    split_project: bool,
+
+    /// Unix file mode set on written output files, in octal (e.g. 0755)
+    #[arg(long, value_name = "MODE", value_parser = parse_mode, conflicts_with = "split_project")]
+    output_permissions: Option<u32>,
+
+    /// Start the output with a UTF-8 byte order mark, for editors that expect one (fails if
+    /// the output starts with a shebang, which must be the very first bytes to work)
+    #[arg(long, conflicts_with_all = ["split_project", "data_uri", "pick_theme"])]
+    output_bom: bool,
+
+    /// Normalize the output to the same bytes on every platform and run: LF line endings,
+    /// one trailing newline, no highlighting, and banner paths relative to the entry point
+    #[arg(long, conflicts_with_all = ["theme", "pick_theme", "output_bom"])]
+    canonical_output: bool,
+
+    /// Enable syntax highlighting with specified theme (a comma-separated list renders the
+    /// input once per theme for comparison)
     /// NOTE: cannot be used together with --output because highlighting writes ANSI escapes which would corrupt output files
     #[arg(short, long, conflicts_with = "output")]
     theme: Option<String>,
 
-    /// List all available themes
+    /// Highlight with a Helix-style TOML theme file; a `base = "<builtin>"` key starts from
+    /// that builtin theme and overrides only what the file sets
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["theme", "output"])]
+    theme_file: Option<PathBuf>,
+
+    /// Theme to highlight with when stdout is a terminal and --theme isn't given
+    /// (overrides SCRIPTIFY_AUTO_THEME; piped or --output output stays plain)
+    #[arg(long)]
+    auto_theme: Option<String>,
+
+    /// Also print the output highlighted with this theme to stderr, when it is a terminal
+    #[arg(long, value_name = "THEME", conflicts_with_all = ["diff", "pick_theme", "output_dir"])]
+    preview: Option<String>,
+
+    /// Shorten string literals longer than N characters in output shown on a terminal
+    /// (stdout, --preview and --pick-theme), never in output written elsewhere
+    #[arg(long, value_name = "N")]
+    truncate_strings: Option<usize>,
+
+    /// Only output lines START to END (counted from 1, inclusive) of the generated script
+    #[arg(
+        long,
+        value_name = "START:END",
+        value_parser = lines::parse_range,
+        conflicts_with_all = ["data_uri", "diff", "output_dir"]
+    )]
+    lines: Option<lines::LineRange>,
+
+    /// Also output N lines before and after the --lines range, dimmed when highlighting
+    #[arg(long, value_name = "N", requires = "lines")]
+    context: Option<usize>,
+
+    /// Warn about colors of the --theme/--preview/auto theme that contrast poorly with
+    /// its background
+    #[arg(long)]
+    theme_contrast_check: bool,
+
+    /// Grammar to highlight the code with (the embedded manifest is always highlighted as TOML)
+    #[arg(long, value_name = "LANG", default_value = "rust")]
+    lang: String,
+
+    /// Show a unified diff between --output and freshly generated output instead of writing it
+    /// (exits non-zero if they differ)
+    #[arg(long, requires = "output", conflicts_with = "compress")]
+    diff: bool,
+
+    /// Highlight the code in --diff output with this theme when colors are on, keeping the
+    /// +/- markers green and red
+    #[arg(long, value_name = "THEME", requires = "diff")]
+    diff_highlight: Option<String>,
+
+    /// Compress the generated output (defaults to gzip when --output ends in .gz)
+    #[arg(long, value_enum, conflicts_with = "theme")]
+    compress: Option<Compression>,
+
+    /// Fail instead of writing output larger than this many bytes (warns from 80% of it)
+    #[arg(long, value_name = "BYTES")]
+    max_output_size: Option<u64>,
+
+    /// Output the plain generated script as a `data:text/x-rust;base64,...` URI
+    #[arg(long, conflicts_with_all = ["theme", "compress", "diff", "pick_theme", "output_dir"])]
+    data_uri: bool,
+
+    /// Pipe the generated output through a shell command and use its stdout instead,
+    /// e.g. `rustfmt --edition 2024` (disables highlighting)
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["theme", "pick_theme", "split_project"])]
+    hook: Option<String>,
+
+    /// Print the wall time spent in each phase (input, inlining, formatting, transforms,
+    /// highlighting, write) to stderr
+    #[arg(long)]
+    timing: bool,
+
+    /// Don't print warnings, notes or progress (warnings still count towards --fail-on-warning)
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// When to color `error:`/`warning:` labels on stderr and --diff output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Print errors and warnings to stderr as JSON lines with file/line/col when known
+    #[arg(long)]
+    json_errors: bool,
+
+    /// Exit with an error if any warning was emitted
+    #[arg(long)]
+    fail_on_warning: bool,
+
+    /// Warn about `unsafe` blocks, fns, impls and traits in the inlined code, with locations
+    #[arg(long)]
+    warn_unsafe: bool,
+
+    /// Fail if the inlined code contains any `unsafe` blocks, fns, impls or traits
+    #[arg(long)]
+    deny_unsafe: bool,
+
+    /// Warn about functions with more than N statements (nested blocks and closures
+    /// included), largest first
+    #[arg(long, value_name = "N")]
+    warn_large_functions: Option<usize>,
+
+    /// Warn about manifest dependencies from git or a registry other than crates.io
+    #[arg(long)]
+    warn_network_deps: bool,
+
+    /// Fail if the manifest has dependencies from git or a registry other than crates.io
+    #[arg(long)]
+    deny_network_deps: bool,
+
+    /// Disable all decoration (highlighting, colors and stderr chatter) for raw, pipe-safe
+    /// output
+    #[arg(long, conflicts_with = "pick_theme")]
+    plain: bool,
+
+    /// List the bin, lib and example targets of a crate directory and which one is the default
+    #[arg(long, group = "listing")]
+    list_targets: bool,
+
+    /// Print the tree of module files that would be inlined and exit without generating
+    #[arg(long, group = "listing")]
+    list_inlined: bool,
+
+    /// Output format for --list-targets and --list-inlined
+    #[arg(long, value_enum, default_value_t = ListFormat::Human, requires = "listing")]
+    format: ListFormat,
+
+    /// Print the external crates the inlined code uses as a JSON array, noting which the
+    /// manifest declares, and exit without generating
+    #[arg(long, conflicts_with_all = ["list_targets", "output_dir", "diff", "pick_theme"])]
+    emit_deps_json: bool,
+
+    /// Debugging aid: print the inlined syntax tree, after the AST transforms, to stderr
+    /// and exit without generating
+    #[arg(long, hide_short_help = true, conflicts_with_all = ["listing", "emit_deps_json", "output_dir", "diff", "pick_theme"])]
+    dump_ast: bool,
+
+    /// List all available themes, each with a highlighted preview when writing to a terminal
     #[arg(long)]
     list_themes: bool,
 
+    /// Rust file whose first lines --list-themes previews with each theme, instead of a
+    /// built-in snippet (shows previews even when piped)
+    #[arg(long, value_name = "FILE", requires = "list_themes")]
+    theme_preview_code: Option<PathBuf>,
+
+    /// Number of lines of each --list-themes preview
+    #[arg(long, value_name = "N", default_value_t = 8, requires = "list_themes")]
+    theme_preview_lines: usize,
+
+    /// Explain what each part of the shebang line does (as resolved from --shebang,
+    /// SCRIPTIFY_SHEBANG, --target and --toolchain), then exit
+    #[arg(long)]
+    explain: bool,
+
+    /// Interactively cycle through themes previewing the input, then print the chosen name
+    #[arg(long, conflicts_with_all = ["output", "theme"])]
+    pick_theme: bool,
+
     /// Path to Cargo.toml for cargo-script generation
     #[arg(short = 'm', long)]
     manifest: Option<PathBuf>,
@@ -39,128 +381,2713 @@ struct Cli {
     #[arg(long, requires = "zscript")]
     stop_at_cwd: bool,
 
-    /// Generate cargo-script with empty manifest
-    #[arg(short = 'e', long, conflicts_with_all = ["manifest", "zscript"])]
-    empty_manifest: bool,
+    /// Fail if -z finds no Cargo.toml, instead of generating a script without frontmatter
+    #[arg(long, requires = "zscript", conflicts_with = "no_frontmatter")]
+    require_manifest: bool,
+
+    /// Search for Cargo.toml from the input's path as given, without resolving symlinks
+    #[arg(long, requires = "zscript")]
+    logical_paths: bool,
+
+    /// Script runner the output is generated for
+    #[arg(long, value_enum, default_value_t = ScriptTarget::CargoScript)]
+    target: ScriptTarget,
+
+    /// Output a POSIX shell script that writes the Rust script to a cache file and runs it
+    /// with the shebang's command, for systems where the shebang itself doesn't work
+    #[arg(long, conflicts_with_all = ["theme", "pick_theme", "split_project", "measure_compile", "verify_run"])]
+    emit_wrapper: bool,
+
+    /// Label after the opening `---` fence of the manifest, for runners that look for a
+    /// section other than `cargo` (letters, digits, '_' and '-')
+    #[arg(long, value_name = "LABEL", default_value = DEFAULT_SECTION, value_parser = parse_section_label)]
+    manifest_section: String,
+
+    /// Toolchain the default shebang targets
+    #[arg(long, value_enum, default_value_t = Toolchain::Stable)]
+    toolchain: Toolchain,
+
+    /// Shebang line for cargo-script output (overrides SCRIPTIFY_SHEBANG and --toolchain)
+    #[arg(long)]
+    shebang: Option<String>,
+
+    /// Where to write the shebang: only to files (--output/--output-dir), always, or never
+    #[arg(long, value_enum, default_value_t = ShebangOn::File)]
+    shebang_on: ShebangOn,
+
+    /// Prepend the crate's README (`package.readme` or README.md) as `//!` docs, for
+    /// crate directory inputs
+    #[arg(long)]
+    include_readme: bool,
+
+    /// Emit only the code, without manifest fences, even if -m/-z/-e are given
+    /// (an explicit --shebang is still prepended where --shebang-on allows)
+    #[arg(long)]
+    no_frontmatter: bool,
+
+    /// Emit only the shebang and manifest block, without the code (fails without -m/-z/-e)
+    #[arg(
+        long,
+        conflicts_with_all = ["no_frontmatter", "split_project", "measure_compile", "verify_run"]
+    )]
+    frontmatter_only: bool,
+
+    /// Reuse the manifest embedded in the input (`---cargo` frontmatter or a `//! ```cargo`
+    /// block) for the output
+    #[arg(long, conflicts_with_all = ["manifest", "empty_manifest", "no_frontmatter"])]
+    reuse_frontmatter: bool,
+
+    /// Generate cargo-script with empty manifest
+    #[arg(short = 'e', long, conflicts_with_all = ["manifest", "zscript"])]
+    empty_manifest: bool,
+
+    /// Set `KEY=VALUE` under `[package.metadata]` of the embedded manifest, e.g. `runner.timeout=30`
+    /// (repeatable; VALUE is parsed as TOML, falling back to a string)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_metadata_entry)]
+    metadata: Vec<(String, toml::Value)>,
+
+    /// Merge the tables of a TOML file into `[package.metadata]` of the embedded manifest,
+    /// before any --metadata entries
+    #[arg(long, value_name = "FILE")]
+    package_metadata_from: Option<PathBuf>,
+
+    /// Put the SHA-256 of the Cargo.lock next to the manifest (or above it, in a workspace)
+    /// in a comment above the code, to show when the dependency set changed
+    #[arg(long)]
+    emit_cargo_lock_hash: bool,
+
+    /// Leave out the `[dependencies]` table when it has no entries
+    #[arg(long)]
+    omit_empty_deps: bool,
+
+    /// Strip the manifest down to what a script uses: drop build targets, dev- and
+    /// build-dependencies and workspace settings, keeping target-specific dependencies and
+    /// features
+    #[arg(long)]
+    minify_manifest: bool,
+
+    /// Build the generated cargo-script in a temporary directory and report the compile time
+    #[arg(long, conflicts_with_all = ["diff", "pick_theme", "output_dir"])]
+    measure_compile: bool,
+
+    /// Build and run the generated cargo-script in a temporary directory, failing (and
+    /// writing nothing) if either step exits non-zero
+    #[arg(long, conflicts_with_all = ["diff", "pick_theme", "output_dir"])]
+    verify_run: bool,
+
+    /// Keep `//` comments that sit directly above items (best effort; doc comments are always kept)
+    #[arg(long)]
+    preserve_mod_comments: bool,
+
+    /// Put a `//` banner naming the module and the file it came from above every inlined module
+    #[arg(long)]
+    group_by_module: bool,
+
+    /// Banner text for --group-by-module: `{module}` is replaced with the module path and
+    /// `{file}` with the file it was inlined from
+    #[arg(long, value_name = "TEMPLATE", requires = "group_by_module", value_parser = parse_banner, allow_hyphen_values = true)]
+    module_banner: Option<String>,
+
+    /// Annotate `pub use` re-exports that go through other re-exports with the item's
+    /// origin, listing each chain on stderr (nothing is rewritten)
+    #[arg(long)]
+    resolve_reexports: bool,
+
+    /// Don't warn when cargo-script output has no `fn main` at the crate root
+    #[arg(long)]
+    no_detect_main: bool,
+
+    /// Keep #[test] functions and generate a `fn main()` that runs them
+    #[arg(long)]
+    wrap_test_harness: bool,
+
+    /// Default argument the script re-runs itself with when started without arguments
+    /// (repeatable, in order)
+    #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+    append_run_args: Vec<String>,
+
+    /// Sort top-level items by name within their kind and visibility, for stable diffs
+    /// (`use`s and macros stay in place)
+    #[arg(long)]
+    alphabetize_items: bool,
+
+    /// Guarantee items keep their source order: rejects reordering options such as
+    /// --alphabetize-items and stops --fmt-backend rustfmt from sorting `use`s and `mod`s
+    #[arg(long, conflicts_with = "alphabetize_items")]
+    preserve_item_order: bool,
+
+    /// Depend on the sibling workspace crates the code uses through absolute `path`
+    /// dependencies, and make relative paths and `workspace = true` entries in the manifest
+    /// work outside the workspace (other crates are never inlined)
+    #[arg(long)]
+    path_deps: bool,
+
+    /// Formatter for the generated code (rustfmt reads rustfmt.toml from the current directory)
+    #[arg(long, value_enum, default_value_t = FmtBackend::Prettyplease)]
+    fmt_backend: FmtBackend,
+
+    /// Edition rustfmt parses and formats the code as, with --fmt-backend rustfmt
+    #[arg(long, default_value = "2024", value_parser = ["2015", "2018", "2021", "2024"])]
+    edition: String,
+
+    /// Warn if running the formatter over its own output changes it (a formatting bug)
+    #[arg(long)]
+    format_check: bool,
+
+    /// Collapse runs of blank lines in the code into a single blank line
+    #[arg(long)]
+    trim_blank_lines: bool,
+
+    /// Warn about output lines longer than N characters (tabs count as --tab-width)
+    #[arg(long, value_name = "N")]
+    max_line_length: Option<usize>,
+
+    /// Split over-long `use` lists one item per line to fit --max-line-length (other long
+    /// lines are only reported)
+    #[arg(long, requires = "max_line_length")]
+    wrap: bool,
+
+    /// Indent the generated code with spaces or tabs (a post-pass over leading whitespace)
+    #[arg(long, value_enum, default_value_t = Indent::Spaces)]
+    indent: Indent,
+
+    /// Spaces per indentation level with --indent spaces
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=16))]
+    tab_width: u8,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Reformat a Rust file with prettyplease, without inlining modules
+    Format {
+        /// Rust source file to format
+        file: PathBuf,
+
+        /// Write the formatted code back to the file instead of stdout
+        #[arg(short, long)]
+        in_place: bool,
+    },
+
+    /// Create a minimal runnable cargo-script (shebang, empty manifest and `fn main`)
+    New {
+        /// Path of the script to create, e.g. `hello.rs`
+        file: PathBuf,
+
+        /// Overwrite the file if it already exists
+        #[arg(short, long)]
+        force: bool,
+
+        /// Open the new script in $VISUAL or $EDITOR
+        #[arg(long)]
+        open: bool,
+    },
+
+    /// Check the installation by running the whole pipeline on a built-in fixture
+    SelfTest,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Compression {
+    Gzip,
+    None,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ScriptTarget {
+    /// Manifest in `---cargo` frontmatter fences (RFC 3424)
+    CargoScript,
+    /// Manifest in a `//! ```cargo` doc comment block
+    RustScript,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum FmtBackend {
+    /// Built-in, no external tools needed
+    Prettyplease,
+    /// The system rustfmt, run over prettyplease's output (falls back to prettyplease)
+    Rustfmt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ShebangOn {
+    /// Only when writing to a file, which may be made executable
+    File,
+    /// Also when printing to stdout
+    Always,
+    /// Never, e.g. when the output is always piped
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ListFormat {
+    /// Aligned table for reading
+    Human,
+    /// A single JSON object for scripts
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    /// Color when writing to a terminal and NO_COLOR is unset
+    Auto,
+    /// Color even when writing to a pipe or file
+    Always,
+    /// Never color
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Indent {
+    Spaces,
+    Tabs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Toolchain {
+    /// Enables `-Zscript` on stable through RUSTC_BOOTSTRAP
+    Stable,
+    /// Uses `cargo +nightly -Zscript` directly
+    Nightly,
+}
+
+fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+
+    // Theme names and theme files go through the same lookup (see `load_theme`)
+    if let Some(file) = cli.theme_file.take() {
+        cli.theme = Some(file.display().to_string());
+    }
+
+    if cli.plain {
+        apply_plain(&mut cli);
+    }
+
+    if cli.list_themes {
+        return list_themes(&cli).map_or_else(
+            |e| {
+                diagnostics::error(e.location(), &e);
+                ExitCode::from(e.exit_code())
+            },
+            |()| ExitCode::SUCCESS,
+        );
+    }
+
+    if cli.explain {
+        explain_shebang(&cli);
+        return ExitCode::SUCCESS;
+    }
+
+    diagnostics::set_quiet(cli.quiet);
+    diagnostics::set_color(use_color(cli.color, &std::io::stderr()));
+    diagnostics::set_json(cli.json_errors);
+    timing::set_enabled(cli.timing && !cli.quiet);
+
+    let result = run(&cli).and_then(|code| enforce_warnings(&cli, code));
+    timing::report();
+
+    result.unwrap_or_else(|e| {
+        diagnostics::error(e.location(), &e);
+        ExitCode::from(e.exit_code())
+    })
+}
+
+/// Resolves `--color` for `stream`: `auto` colors terminals unless NO_COLOR is set
+fn use_color(choice: ColorChoice, stream: &impl IsTerminal) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            stream.is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+    }
+}
+
+fn enforce_warnings(cli: &Cli, code: ExitCode) -> Result<ExitCode> {
+    let warnings = diagnostics::warning_count();
+    if cli.fail_on_warning && warnings > 0 {
+        return Err(format!("{warnings} warning(s) emitted with --fail-on-warning").into());
+    }
+
+    Ok(code)
+}
+
+/// Overrides every decoration option so only the raw generated bytes are emitted
+fn apply_plain(cli: &mut Cli) {
+    cli.theme = None;
+    cli.auto_theme = None;
+    cli.theme_contrast_check = false;
+    cli.quiet = true;
+    cli.color = ColorChoice::Never;
+    cli.preview = None;
+    cli.timing = false;
+    cli.group_by_module = false;
+    cli.module_banner = None;
+    cli.diff_highlight = None;
+    cli.truncate_strings = None;
+}
+
+/// The explicit `--theme`, or the auto theme when writing to a terminal
+fn resolve_theme(cli: &Cli) -> Option<String> {
+    if let Some(theme) = &cli.theme {
+        return Some(theme.clone());
+    }
+
+    let compressed = matches!(cli.compress, Some(Compression::Gzip));
+    if cli.plain
+        || cli.canonical_output
+        || cli.data_uri
+        || cli.hook.is_some()
+        || cli.emit_wrapper
+        || compressed
+        || cli.output.is_some()
+        || !std::io::stdout().is_terminal()
+    {
+        return None;
+    }
+
+    cli.auto_theme
+        .clone()
+        .or_else(|| std::env::var("SCRIPTIFY_AUTO_THEME").ok())
+        .filter(|theme| !theme.is_empty())
+}
+
+fn run(cli: &Cli) -> Result<ExitCode> {
+    if cli.target == ScriptTarget::RustScript && cli.manifest_section != DEFAULT_SECTION {
+        return Err(ScriptifyError::Usage(
+            "--manifest-section only applies to --target cargo-script".to_string(),
+        ));
+    }
+
+    if let Some(command) = &cli.command {
+        return run_command(cli, command);
+    }
+
+    if cli.output_permissions.is_some() && cli.output.is_none() && cli.output_dir.is_none() {
+        return Err(ScriptifyError::Usage(
+            "--output-permissions requires --output or --output-dir".to_string(),
+        ));
+    }
+
+    if cli.list_targets {
+        return list_targets(cli, required_input(cli)?);
+    }
+
+    if cli.list_inlined {
+        return list_inlined(cli, required_input(cli)?);
+    }
+
+    if cli.emit_deps_json {
+        return emit_deps_json(cli, required_input(cli)?);
+    }
+
+    if cli.dump_ast {
+        return dump_ast(cli, required_input(cli)?);
+    }
+
+    if let Some(out_dir) = cli.output_dir.as_deref().filter(|_| cli.split_project) {
+        return write_split_project(cli, required_input(cli)?, out_dir);
+    }
+
+    if let Some(out_dir) = &cli.output_dir {
+        let inputs = match &cli.input_glob {
+            Some(pattern) => glob::expand(pattern, !cli.no_ignore)?,
+            None => vec![required_input(cli)?.clone()],
+        };
+        if inputs.is_empty() {
+            return Err(ScriptifyError::Usage(
+                "--input-glob matched no files".to_string(),
+            ));
+        }
+        return run_batch(cli, &inputs, out_dir);
+    }
+
+    let input_path = required_input(cli)?;
+
+    if cli.pick_theme {
+        let output_content = generate(cli, input_path, None)?;
+        let output_content = displayed(cli, &output_content);
+        if let Some(theme) = picker::pick_theme(&output_content, &cli.lang)? {
+            println!("{theme}");
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if cli.diff {
+        let out_path = cli
+            .output
+            .as_ref()
+            .ok_or_else(|| ScriptifyError::Usage("--diff requires --output".to_string()))?;
+        return diff_against_output(cli, input_path, out_path);
+    }
+
+    let theme = resolve_theme(cli);
+    if cli.theme_contrast_check {
+        check_theme_contrast(theme.iter().chain(&cli.preview))?;
+    }
+    let cache_key = match &cli.output {
+        Some(out_path) if cli.cache => {
+            let key = cache_key(cli, input_path)?;
+            if cache::is_fresh(out_path, &key) {
+                diagnostics::note(format!("'{}' is up to date", out_path.display()));
+                return Ok(ExitCode::SUCCESS);
+            }
+            cache::set_tracking(true);
+            Some(key)
+        }
+        _ => None,
+    };
+    let script = generate(cli, input_path, None)?;
+    let bom = output_bom(cli, &script)?;
+    let builds = [
+        ("--measure-compile", cli.measure_compile),
+        ("--verify-run", cli.verify_run),
+    ];
+    if let Some((flag, _)) = builds.iter().find(|(_, enabled)| *enabled)
+        && (cli.target != ScriptTarget::CargoScript || split_frontmatter(&script).is_none())
+    {
+        return Err(ScriptifyError::Usage(format!(
+            "{flag} requires cargo-script output (use -m, -z or -e)"
+        )));
+    }
+
+    if cli.verify_run {
+        measure::verify_run(&script, cli.toolchain == Toolchain::Nightly)?;
+    }
+
+    if let Some(preview_theme) = &cli.preview {
+        load_theme(preview_theme)?;
+        if std::io::stderr().is_terminal() {
+            let preview = timing::time(Phase::Highlight, || {
+                highlight_output(&displayed(cli, &script), preview_theme, &cli.lang)
+            })?;
+            eprint!("{preview}");
+        }
+    }
+
+    let compression = resolve_compression(cli);
+    let on_terminal = cli.output.is_none()
+        && !cli.data_uri
+        && compression == Compression::None
+        && std::io::stdout().is_terminal();
+    if cli.truncate_strings.is_some()
+        && !on_terminal
+        && cli
+            .preview
+            .as_ref()
+            .is_none_or(|_| !std::io::stderr().is_terminal())
+    {
+        diagnostics::warn("--truncate-strings has no effect: no output is shown on a terminal");
+    }
+    let shown = if on_terminal {
+        displayed(cli, &script)
+    } else {
+        script.clone()
+    };
+
+    let output_content = timing::time(Phase::Highlight, || {
+        if cli.data_uri {
+            Ok(format!(
+                "data:text/x-rust;base64,{}\n",
+                base64::engine::general_purpose::STANDARD.encode(&script)
+            ))
+        } else if let Some(themes) = theme.as_deref().filter(|t| t.contains(',')) {
+            let shown = match cli.lines {
+                Some(range) => lines::select(&shown, None, range, cli.context.unwrap_or(0))?,
+                None => shown,
+            };
+            compare_themes(&shown, themes, &cli.lang)
+        } else if let Some(range) = cli.lines {
+            let highlighted = theme
+                .as_deref()
+                .map(|theme| highlight_output(&shown, theme, &cli.lang))
+                .transpose()?;
+            lines::select(
+                &shown,
+                highlighted.as_deref(),
+                range,
+                cli.context.unwrap_or(0),
+            )
+        } else {
+            apply_syntax_highlighting(&shown, theme.as_deref(), &cli.lang)
+        }
+    })
+    .map(|content| format!("{bom}{content}"))?;
+    let output_bytes = match compression {
+        Compression::Gzip => gzip(output_content.as_bytes())?,
+        Compression::None => output_content.into_bytes(),
+    };
+
+    check_output_size(cli, output_bytes.len(), cli.output.as_deref())?;
+
+    timing::time(Phase::Write, || match &cli.output {
+        Some(out_path) => write_output(out_path, &output_bytes)
+            .and_then(|()| set_output_permissions(out_path, cli.output_permissions))
+            .map_err(ScriptifyError::io(format!(
+                "failed to write '{}'",
+                out_path.display()
+            ))),
+        None => Ok(std::io::stdout().write_all(&output_bytes)?),
+    })?;
+    if let (Some(key), Some(out_path)) = (&cache_key, &cli.output) {
+        cache::store(out_path, key)?;
+    }
+
+    if cli.measure_compile {
+        let elapsed = measure::measure_compile(&script, cli.toolchain == Toolchain::Nightly)?;
+        eprintln!("compiled in {:.2}s", elapsed.as_secs_f64());
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// --cache: hash of what besides its source files decides the output, i.e. the parsed
+/// options, the manifest file as found for the input, and the shebang
+fn cache_key(cli: &Cli, input_path: &Path) -> Result<String> {
+    if is_stdin(input_path) || is_url(input_path) {
+        return Err(ScriptifyError::Usage(
+            "--cache requires a file or crate directory input".to_string(),
+        ));
+    }
+
+    let manifest = if cli.no_frontmatter {
+        ManifestOption::None
+    } else {
+        resolve_manifest(cli, &resolve_input_path(cli, input_path)?)?
+    };
+    let manifest = match manifest {
+        ManifestOption::Path(path) => Some(read_manifest(&path)?),
+        ManifestOption::Content(content) => Some(content),
+        ManifestOption::Empty => Some(String::new()),
+        ManifestOption::None => None,
+    };
+    Ok(cache::key(
+        &format!("{cli:?}"),
+        manifest.as_deref(),
+        &get_shebang(cli),
+    ))
+}
+
+/// Prints a unified diff between the existing output file and freshly generated output.
+/// Exits with failure when they differ.
+fn diff_against_output(cli: &Cli, input_path: &Path, out_path: &Path) -> Result<ExitCode> {
+    let output_content = generate(cli, input_path, None)?;
+    let output_content = format!("{}{output_content}", output_bom(cli, &output_content)?);
+    let existing = read_existing_output(out_path)?;
+    let label = out_path.display().to_string();
+    if let Some(theme) = &cli.diff_highlight {
+        load_theme(theme)?;
+    }
+
+    let Some(diff) = diff::unified_diff(
+        &existing,
+        &output_content,
+        &label,
+        &format!("{label} (generated)"),
+    ) else {
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    if !use_color(cli.color, &std::io::stdout()) {
+        print!("{diff}");
+    } else if let Some(theme) = &cli.diff_highlight {
+        let highlighted = timing::time(Phase::Highlight, || {
+            highlight_diff(&diff, (&existing, &output_content), theme, &cli.lang)
+        })?;
+        print!("{highlighted}");
+    } else {
+        print!("{}", diff::colorize(&diff));
+    }
+
+    Ok(ExitCode::FAILURE)
+}
+
+/// --diff-highlight: the diff with the lines of both sides as `theme` highlights them.
+/// Highlighting that doesn't keep the line structure falls back to the plain colors.
+fn highlight_diff(
+    diff: &str,
+    (old, new): (&str, &str),
+    theme: &str,
+    language: &str,
+) -> Result<String> {
+    let old_highlighted = highlight_output(old, theme, language)?;
+    let new_highlighted = highlight_output(new, theme, language)?;
+    if old_highlighted.lines().count() != old.lines().count()
+        || new_highlighted.lines().count() != new.lines().count()
+    {
+        return Ok(diff::colorize(diff));
+    }
+    Ok(diff::colorize_highlighted(
+        diff,
+        &old_highlighted,
+        &new_highlighted,
+    ))
+}
+
+/// `script` as shown on a terminal: with --truncate-strings, long string literals are cut
+/// down to a marker
+fn displayed(cli: &Cli, script: &str) -> String {
+    match cli.truncate_strings {
+        Some(limit) => truncate_strings::truncate_strings(script, limit),
+        None => script.to_string(),
+    }
+}
+
+/// --canonical-output: CRLF line endings (e.g. from a manifest or hook) become LF and the
+/// text ends in exactly one newline. rustc reads CRLF in source as LF, so this never
+/// changes what the code means.
+fn canonical_text(text: &str) -> String {
+    let mut text = text.replace("\r\n", "\n");
+    text.truncate(text.trim_end_matches('\n').len());
+    text.push('\n');
+    text
+}
+
+/// The byte order mark --output-bom puts before `script`. A script starting with a shebang
+/// is refused: the kernel only recognizes `#!` as the very first bytes of a file.
+fn output_bom(cli: &Cli, script: &str) -> Result<&'static str> {
+    if !cli.output_bom {
+        return Ok("");
+    }
+    if script.starts_with("#!") && !script.starts_with("#![") {
+        return Err(ScriptifyError::Usage(
+            "--output-bom can't be used with a shebang, which stops working after a byte order mark (add --shebang-on never)".to_string(),
+        ));
+    }
+    Ok("\u{feff}")
+}
+
+/// Compresses `data` into a single gzip member
+fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Writes `bytes` to `path`, streaming into named pipes rather than creating/truncating a file
+fn write_output(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if is_fifo(path) {
+        // Opening blocks until a reader connects, then the pipe is written like stdout
+        let mut pipe = std::fs::OpenOptions::new().write(true).open(path)?;
+        pipe.write_all(bytes)?;
+        return pipe.flush();
+    }
+
+    write_atomic(path, bytes)
+}
+
+/// Writes to a temporary file next to `path` and renames it over `path`, so readers see
+/// either the old or the complete new contents. An existing file's permissions are kept
+/// and a symlink is written through rather than replaced.
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let target = match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => std::fs::canonicalize(path)?,
+        _ => path.to_path_buf(),
+    };
+    let file_name = target.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "output path has no file name",
+        )
+    })?;
+    let temp = target.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = std::fs::write(&temp, bytes)
+        .and_then(|()| match std::fs::metadata(&target) {
+            Ok(metadata) => std::fs::set_permissions(&temp, metadata.permissions()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        })
+        .and_then(|()| std::fs::rename(&temp, &target));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// Reads the current contents of an output file, treating a missing file as empty
+fn read_existing_output(out_path: &Path) -> Result<String> {
+    if is_fifo(out_path) {
+        return Err(ScriptifyError::Usage(format!(
+            "--diff cannot compare against named pipe '{}'",
+            out_path.display()
+        )));
+    }
+
+    match std::fs::read_to_string(out_path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(ScriptifyError::io(format!(
+            "failed to read '{}'",
+            out_path.display()
+        ))(e)),
+    }
+}
+
+fn resolve_compression(cli: &Cli) -> Compression {
+    if let Some(compression) = cli.compress {
+        return compression;
+    }
+
+    let gz_output = cli
+        .output
+        .as_ref()
+        .is_some_and(|path| path.extension().is_some_and(|ext| ext == "gz"));
+
+    if gz_output {
+        Compression::Gzip
+    } else {
+        Compression::None
+    }
+}
+
+fn run_command(cli: &Cli, command: &Command) -> Result<ExitCode> {
+    match command {
+        Command::Format { file, in_place } => format_file(file, *in_place),
+        Command::New { file, force, open } => new_script(cli, file, *force, *open),
+        Command::SelfTest if self_test::self_test() => Ok(ExitCode::SUCCESS),
+        Command::SelfTest => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// Parses and unparses a single file through prettyplease, leaving `mod` declarations alone
+fn format_file(file: &Path, in_place: bool) -> Result<ExitCode> {
+    let source = std::fs::read_to_string(file).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ScriptifyError::InputNotFound(file.to_path_buf()),
+        _ => ScriptifyError::io(format!("failed to read '{}'", file.display()))(e),
+    })?;
+    let syntax = syn::parse_file(&source).map_err(|e| inline::parse_error(file, &e))?;
+    let formatted = formatter::unparse(&syntax)?;
+
+    if in_place {
+        write_output(file, formatted.as_bytes()).map_err(ScriptifyError::io(format!(
+            "failed to write '{}'",
+            file.display()
+        )))?;
+    } else {
+        print!("{formatted}");
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes an executable script skeleton in the --target format, with the shebang of
+/// --toolchain/--shebang
+fn new_script(cli: &Cli, file: &Path, force: bool, open: bool) -> Result<ExitCode> {
+    const SKELETON: &str = "fn main() {\n    println!(\"Hello, world!\");\n}\n";
+
+    if !force && std::fs::symlink_metadata(file).is_ok() {
+        return Err(format!(
+            "'{}' already exists (use --force to overwrite it)",
+            file.display()
+        )
+        .into());
+    }
+
+    let style = ScriptStyle {
+        shebang: Some(get_shebang(cli)),
+        ..script_style(cli)
+    };
+    let script = format_output(SKELETON, ManifestOption::Empty, &style)?;
+    write_output(file, script.as_bytes())
+        .and_then(|()| set_executable(file))
+        .map_err(ScriptifyError::io(format!(
+            "failed to write '{}'",
+            file.display()
+        )))?;
+
+    if open {
+        open_in_editor(file)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    // Executable by whoever may read it, like `chmod +x` under the usual umask
+    permissions.set_mode(permissions.mode() | (permissions.mode() & 0o444) >> 2);
+    std::fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Applies --output-permissions to a written output file; named pipes are left alone
+#[cfg(unix)]
+fn set_output_permissions(path: &Path, mode: Option<u32>) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    match mode {
+        Some(mode) if !is_fifo(path) => {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_output_permissions(_path: &Path, mode: Option<u32>) -> std::io::Result<()> {
+    static UNSUPPORTED: std::sync::Once = std::sync::Once::new();
+
+    if mode.is_some() {
+        UNSUPPORTED.call_once(|| {
+            diagnostics::warn("--output-permissions has no effect on this platform");
+        });
+    }
+    Ok(())
+}
+
+/// Runs $VISUAL (or $EDITOR) on `file` and waits for it to exit
+fn open_in_editor(file: &Path) -> Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty());
+    let Some(editor) = editor else {
+        diagnostics::warn("--open needs $VISUAL or $EDITOR to be set");
+        return Ok(());
+    };
+
+    // Editors are often configured with arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(file)
+        .status()
+        .map_err(ScriptifyError::io(format!(
+            "failed to run editor '{editor}'"
+        )))?;
+    if !status.success() {
+        diagnostics::warn(format!("editor '{editor}' exited with {status}"));
+    }
+    Ok(())
+}
+
+fn required_input(cli: &Cli) -> Result<&PathBuf> {
+    cli.input
+        .as_ref()
+        .ok_or_else(|| ScriptifyError::Usage("<INPUT> is required".to_string()))
+}
+
+/// Generates every input into `out_dir`, named after each input's file stem
+fn run_batch(cli: &Cli, inputs: &[PathBuf], out_dir: &Path) -> Result<ExitCode> {
+    let mut targets: HashMap<PathBuf, &Path> = HashMap::new();
+    for input in inputs {
+        let out_path = out_dir.join(batch_file_name(cli, input)?);
+        if let Some(previous) = targets.insert(out_path.clone(), input) {
+            return Err(format!(
+                "'{}' and '{}' would both be written to '{}'",
+                previous.display(),
+                input.display(),
+                out_path.display()
+            )
+            .into());
+        }
+    }
+
+    std::fs::create_dir_all(out_dir).map_err(ScriptifyError::io(format!(
+        "failed to create '{}'",
+        out_dir.display()
+    )))?;
+
+    for input in inputs {
+        let out_path = out_dir.join(batch_file_name(cli, input)?);
+        let output_content = generate(cli, input, None)?;
+        let output_content = format!("{}{output_content}", output_bom(cli, &output_content)?);
+        check_output_size(cli, output_content.len(), Some(&out_path))?;
+        timing::time(Phase::Write, || {
+            write_output(&out_path, output_content.as_bytes())
+                .and_then(|()| set_output_permissions(&out_path, cli.output_permissions))
+        })
+        .map_err(ScriptifyError::io(format!(
+            "failed to write '{}'",
+            out_path.display()
+        )))?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes `<DIR>/src/main.rs` and `<DIR>/Cargo.toml`, without shebang or frontmatter.
+/// A manifest without a `[package]` table gets one named after the directory.
+fn write_split_project(cli: &Cli, input: &Path, out_dir: &Path) -> Result<ExitCode> {
+    let (code, manifest) = generate_code(cli, input)?;
+    if cli.warn_network_deps || cli.deny_network_deps {
+        check_network_deps(cli, &manifest)?;
+    }
+    let manifest = match manifest {
+        ManifestOption::None => ManifestOption::Empty,
+        manifest => manifest,
+    };
+    let mut manifest_content = manifest_content(&manifest, &script_style(cli))?.unwrap_or_default();
+
+    let manifest_table: toml::Table = toml::from_str(&manifest_content)?;
+    if !manifest_table.contains_key("package") {
+        manifest_content = format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2024\"\n\n{manifest_content}",
+            project_name(out_dir)
+        );
+    }
+
+    let src_dir = out_dir.join("src");
+    std::fs::create_dir_all(&src_dir).map_err(ScriptifyError::io(format!(
+        "failed to create '{}'",
+        src_dir.display()
+    )))?;
+
+    for (path, mut content) in [
+        (src_dir.join("main.rs"), code),
+        (out_dir.join("Cargo.toml"), manifest_content),
+    ] {
+        if cli.canonical_output {
+            content = canonical_text(&content);
+        }
+        check_output_size(cli, content.len(), Some(&path))?;
+        timing::time(Phase::Write, || write_output(&path, content.as_bytes())).map_err(
+            ScriptifyError::io(format!("failed to write '{}'", path.display())),
+        )?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Package name for a generated project, from its directory name like `cargo new`
+fn project_name(dir: &Path) -> String {
+    let name: String = std::path::absolute(dir)
+        .ok()
+        .and_then(|dir| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("script{name}")
+    }
+}
+
+/// Enforces --max-output-size, warning once output reaches 80% of the limit
+fn check_output_size(cli: &Cli, size: usize, out_path: Option<&Path>) -> Result<()> {
+    let Some(limit) = cli.max_output_size else {
+        return Ok(());
+    };
+    let target = out_path.map_or_else(|| "stdout".to_string(), |p| format!("'{}'", p.display()));
+    let size = size as u64;
+
+    if size > limit {
+        return Err(format!(
+            "output for {target} is {size} bytes, exceeding --max-output-size of {limit} bytes"
+        )
+        .into());
+    }
+
+    if size * 5 >= limit * 4 {
+        diagnostics::warn(format!(
+            "output for {target} is {size} bytes, {}% of --max-output-size",
+            size * 100 / limit.max(1)
+        ));
+    }
+
+    Ok(())
+}
+
+fn batch_file_name(cli: &Cli, input: &Path) -> Result<String> {
+    if cli.out_stem_from_manifest
+        && let Some(name) = manifest_package_name(input)?
+    {
+        return Ok(format!("{name}.rs"));
+    }
+
+    let input_abs = input.canonicalize().map_err(ScriptifyError::io(format!(
+        "failed to resolve path '{}'",
+        input.display()
+    )))?;
+    let stem = input_abs
+        .file_stem()
+        .ok_or_else(|| format!("cannot derive an output name from '{}'", input.display()))?;
+
+    Ok(format!("{}.rs", stem.to_string_lossy()))
+}
+
+/// Puts the crate's README in front of `code` as `//!` docs
+fn prepend_readme(input: &Path, code: String) -> Result<String> {
+    if !input.is_dir() {
+        diagnostics::warn("--include-readme only applies to crate directory inputs");
+        return Ok(code);
+    }
+
+    let Some(readme_path) = readme_path(input)? else {
+        diagnostics::warn(format!("no README found in '{}'", input.display()));
+        return Ok(code);
+    };
+    cache::track(&readme_path);
+    let readme = std::fs::read_to_string(&readme_path).map_err(ScriptifyError::io(format!(
+        "failed to read '{}'",
+        readme_path.display()
+    )))?;
+
+    let mut docs: String = readme
+        .lines()
+        .map(|line| match line.trim_end() {
+            "" => "//!\n".to_string(),
+            line => format!("//! {line}\n"),
+        })
+        .collect();
+    docs.push('\n');
+    docs.push_str(&code);
+    Ok(docs)
+}
+
+/// The README named by `package.readme`, or the first of Cargo's default README names
+fn readme_path(crate_dir: &Path) -> Result<Option<PathBuf>> {
+    let manifest_path = crate_dir.join("Cargo.toml");
+    let manifest: toml::Value = toml::from_str(&read_manifest(&manifest_path)?)?;
+    match manifest
+        .get("package")
+        .and_then(|package| package.get("readme"))
+    {
+        Some(toml::Value::String(path)) => return Ok(Some(crate_dir.join(path))),
+        Some(toml::Value::Boolean(false)) => return Ok(None),
+        _ => {}
+    }
+
+    Ok(["README.md", "README.txt", "README"]
+        .iter()
+        .map(|name| crate_dir.join(name))
+        .find(|path| path.is_file()))
+}
+
+/// `package.name` of a crate directory's Cargo.toml, if `input` is one
+fn manifest_package_name(input: &Path) -> Result<Option<String>> {
+    let manifest_path = input.join("Cargo.toml");
+    if !input.is_dir() || !manifest_path.is_file() {
+        return Ok(None);
+    }
+
+    let manifest: toml::Value = toml::from_str(&read_manifest(&manifest_path)?)?;
+    Ok(manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_str())
+        .map(str::to_string))
+}
+
+fn generate(cli: &Cli, input_path: &Path, theme: Option<&str>) -> Result<String> {
+    let (code, manifest) = generate_code(cli, input_path)?;
+    if cli.warn_network_deps || cli.deny_network_deps {
+        check_network_deps(cli, &manifest)?;
+    }
+    if let Some(flag) = [
+        ("--metadata", !cli.metadata.is_empty()),
+        (
+            "--package-metadata-from",
+            cli.package_metadata_from.is_some(),
+        ),
+    ]
+    .into_iter()
+    .find_map(|(flag, used)| used.then_some(flag))
+        && matches!(manifest, ManifestOption::None)
+    {
+        return Err(ScriptifyError::Usage(format!(
+            "{flag} requires cargo-script output (use -m, -z or -e)"
+        )));
+    }
+    if cli.frontmatter_only && matches!(manifest, ManifestOption::None) {
+        return Err(ScriptifyError::Usage(
+            "--frontmatter-only requires a manifest (use -m, -z or -e)".to_string(),
+        ));
+    }
+    let code = if cli.frontmatter_only {
+        String::new()
+    } else {
+        if !cli.no_detect_main && !matches!(manifest, ManifestOption::None) {
+            detect_main(&code);
+        }
+        code
+    };
+
+    let output = timing::time(Phase::Transforms, || {
+        let mut output = prepare_output(&code, (theme, &cli.lang), manifest, &script_style(cli))?;
+        if cli.emit_wrapper {
+            output = wrapper::shell_wrapper(&output, &get_shebang(cli));
+        }
+        match &cli.hook {
+            Some(command) => hook::run_hook(command, &output),
+            None => Ok(output),
+        }
+    })?;
+    let output = if cli.canonical_output {
+        canonical_text(&output)
+    } else {
+        output
+    };
+
+    if let Some(limit) = cli.max_line_length {
+        check_line_length(&output, limit, cli.tab_width.into());
+    }
+    Ok(output)
+}
+
+/// Inlines the input and resolves the manifest that goes with it
+fn generate_code(cli: &Cli, input_path: &Path) -> Result<(String, ManifestOption)> {
+    let (input, (mut code, embedded)) = if is_stdin(input_path) {
+        let logical_path = cli
+            .stdin_filename
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(STDIN_LABEL));
+        let inlined = inline_stdin(cli, &logical_path)?;
+        (logical_path, inlined)
+    } else if is_url(input_path) {
+        if cli.inline_path_deps {
+            diagnostics::warn("--inline-path-deps has no effect on URL inputs");
+        }
+        let inlined = inline_url(cli, input_path)?;
+        (input_path.to_path_buf(), inlined)
+    } else {
+        let input = timing::time(Phase::Input, || resolve_input_path(cli, input_path))?;
+        let mut inlined = inline_modules(cli, &input)?;
+        if cli.include_readme {
+            inlined.0 = timing::time(Phase::Transforms, || prepend_readme(input_path, inlined.0))?;
+        }
+        (input, inlined)
+    };
+
+    code = timing::time(Phase::Transforms, || reformat_code(cli, code));
+
+    let manifest = if cli.no_frontmatter {
+        if cli.manifest.is_some() || cli.zscript || cli.empty_manifest {
+            diagnostics::warn("--no-frontmatter overrides -m/-z/-e, no manifest is embedded");
+        }
+        if let Some(shebang) = cli.shebang.as_ref().filter(|_| wants_shebang(cli)) {
+            check_shebang_length(shebang);
+            code = format!("{shebang}\n{code}");
+        }
+        ManifestOption::None
+    } else if let Some(embedded) = embedded {
+        ManifestOption::Content(embedded)
+    } else {
+        if cli.reuse_frontmatter {
+            diagnostics::warn("--reuse-frontmatter found no embedded manifest in the input");
+        }
+        timing::time(Phase::Input, || resolve_manifest(cli, &input))?
+    };
+
+    if cli.path_deps && cli.canonical_output {
+        diagnostics::warn(
+            "--canonical-output: --path-deps writes absolute paths into the manifest, which differ between machines",
+        );
+    }
+    let manifest = if cli.path_deps {
+        timing::time(Phase::Input, || link_path_deps(&code, manifest, &input))?
+    } else {
+        manifest
+    };
+
+    if cli.emit_cargo_lock_hash {
+        code = timing::time(Phase::Input, || add_lock_hash(code, &manifest, &input))?;
+    }
+    Ok((code, manifest))
+}
+
+/// --emit-cargo-lock-hash: starts `code` (after a shebang) with a comment holding the hash
+/// of the Cargo.lock found from the manifest's directory upwards, as cargo places it at the
+/// workspace root. Without a manifest file, the search starts next to the input.
+fn add_lock_hash(code: String, manifest: &ManifestOption, input: &Path) -> Result<String> {
+    let start = match manifest {
+        ManifestOption::Path(path) => path.parent(),
+        _ => input.parent(),
+    }
+    .unwrap_or(Path::new(""));
+    let start = std::path::absolute(start).unwrap_or_else(|_| start.to_path_buf());
+
+    let Some(lockfile) = start
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file())
+    else {
+        diagnostics::warn(format!(
+            "--emit-cargo-lock-hash: no Cargo.lock found in '{}' or its parent directories",
+            start.display()
+        ));
+        return Ok(code);
+    };
+
+    cache::track(&lockfile);
+    let lock = std::fs::read(&lockfile).map_err(ScriptifyError::io(format!(
+        "failed to read '{}'",
+        lockfile.display()
+    )))?;
+    let banner = format!("// Cargo.lock sha256: {:x}\n", Sha256::digest(&lock));
+
+    // A shebang prepended under --no-frontmatter has to stay the first line
+    let shebang_len = if code.starts_with("#!") && !code.starts_with("#![") {
+        code.find('\n').map_or(code.len(), |end| end + 1)
+    } else {
+        0
+    };
+    let (shebang, rest) = code.split_at(shebang_len);
+    Ok(format!("{shebang}{banner}{rest}"))
+}
+
+/// --path-deps: adds the sibling workspace crates `code` uses to the manifest and makes
+/// its workspace-relative dependencies resolve from anywhere
+fn link_path_deps(code: &str, manifest: ManifestOption, input: &Path) -> Result<ManifestOption> {
+    let input_dir = input.parent().unwrap_or(Path::new(""));
+    let (content, manifest_dir) = match &manifest {
+        ManifestOption::Path(path) => {
+            (read_manifest(path)?, path.parent().unwrap_or(Path::new("")))
+        }
+        ManifestOption::Content(content) => (content.clone(), input_dir),
+        ManifestOption::Empty => (String::new(), input_dir),
+        ManifestOption::None => {
+            diagnostics::warn(
+                "--path-deps has no manifest to add dependencies to (use -m, -z or -e)",
+            );
+            return Ok(manifest);
+        }
+    };
+
+    let file = syn::parse_file(code).map_err(|e| inline::parse_error(input, &e))?;
+    let mut table: toml::Table = toml::from_str(&content)?;
+    let changes =
+        workspace::link_path_dependencies(&mut table, manifest_dir, &deps::external_crates(&file))?;
+    if changes.is_empty() {
+        return Ok(manifest);
+    }
+
+    for change in &changes {
+        diagnostics::note(format!("--path-deps: {change}"));
+    }
+    toml::to_string(&table)
+        .map(ManifestOption::Content)
+        .map_err(|e| ScriptifyError::ManifestError(format!("failed to write manifest: {e}")))
+}
+
+/// The line-based passes over the formatted code: --trim-blank-lines and --indent
+fn reformat_code(cli: &Cli, mut code: String) -> String {
+    if cli.trim_blank_lines {
+        code = trim_blank_lines(&code);
+    }
+
+    if cli.indent == Indent::Tabs || cli.tab_width != 4 {
+        if cli.indent == Indent::Tabs && cli.tab_width != 4 {
+            diagnostics::warn("--tab-width has no effect with --indent tabs");
+        }
+        code = indent::reindent(&code, cli.indent == Indent::Tabs, cli.tab_width.into());
+    }
+
+    if let Some(limit) = cli.max_line_length.filter(|_| cli.wrap) {
+        let indent = match cli.indent {
+            Indent::Tabs => "\t".to_string(),
+            Indent::Spaces => " ".repeat(cli.tab_width.into()),
+        };
+        code = line_length::wrap_use_lists(&code, limit, &indent);
+    }
+    code
+}
+
+/// Warns when a script has no `fn main` at its root, which cargo builds as a binary that
+/// fails to link, and notes the runtime attribute an `async fn main` runs on
+fn detect_main(code: &str) {
+    fn nested_mains(items: &[syn::Item], parent: &str, found: &mut Vec<String>) {
+        for item in items {
+            match item {
+                syn::Item::Fn(function) if function.sig.ident == "main" => {
+                    found.push(format!("{parent}::main"));
+                }
+                syn::Item::Mod(module) => {
+                    if let Some((_, items)) = &module.content {
+                        nested_mains(items, &format!("{parent}::{}", module.ident), found);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let Ok(file) = syn::parse_file(code) else {
+        return;
+    };
+    let root_main = file.items.iter().find_map(|item| match item {
+        syn::Item::Fn(function) if function.sig.ident == "main" => Some(function),
+        _ => None,
+    });
+
+    match root_main {
+        Some(main) if main.sig.asyncness.is_some() => {
+            let runtime = main.attrs.iter().find(|attr| {
+                attr.path()
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "main")
+            });
+            match runtime {
+                Some(attr) => {
+                    let path: Vec<String> = attr
+                        .path()
+                        .segments
+                        .iter()
+                        .map(|segment| segment.ident.to_string())
+                        .collect();
+                    diagnostics::note(format!("`async fn main` runs on `#[{}]`", path.join("::")));
+                }
+                None => diagnostics::warn(
+                    "`async fn main` has no runtime attribute such as `#[tokio::main]` and won't compile",
+                ),
+            }
+        }
+        Some(_) => {}
+        None => {
+            let mut nested = Vec::new();
+            nested_mains(&file.items, "crate", &mut nested);
+            let found = match nested.as_slice() {
+                [] => String::new(),
+                nested => format!(
+                    " (only `{}`, which isn't the entry point)",
+                    nested.join("`, `")
+                ),
+            };
+            diagnostics::warn(format!(
+                "the script has no `fn main`{found}, so it can't run; is the input a library? Add a `fn main`, use --wrap-test-harness to run its tests, or silence this with --no-detect-main"
+            ));
+        }
+    }
+}
+
+/// Warns about the first few lines of the output longer than --max-line-length
+fn check_line_length(output: &str, limit: usize, tab_width: usize) {
+    const MAX_REPORTED: usize = 5;
+
+    let long = line_length::long_lines(output, limit, tab_width);
+    for (line, width) in long.iter().take(MAX_REPORTED) {
+        diagnostics::warn(format!(
+            "line {line} is {width} characters long, over --max-line-length {limit}"
+        ));
+    }
+    if long.len() > MAX_REPORTED {
+        diagnostics::warn(format!(
+            "{} lines exceed --max-line-length {limit} ({} more not shown)",
+            long.len(),
+            long.len() - MAX_REPORTED
+        ));
+    }
+}
+
+fn script_style(cli: &Cli) -> ScriptStyle {
+    ScriptStyle {
+        shebang: wants_shebang(cli).then(|| get_shebang(cli)),
+        target: cli.target,
+        section: cli.manifest_section.clone(),
+        omit_empty_deps: cli.omit_empty_deps,
+        minify_manifest: cli.minify_manifest,
+        metadata: cli.metadata.clone(),
+        metadata_from: cli.package_metadata_from.clone(),
+    }
+}
+
+fn is_stdin(input: &Path) -> bool {
+    input == Path::new(STDIN_INPUT)
+}
+
+fn is_url(input: &Path) -> bool {
+    input.to_str().is_some_and(fetch::is_url)
+}
+
+fn resolve_input_path(cli: &Cli, input: &Path) -> Result<PathBuf> {
+    if !input.exists() {
+        return Err(ScriptifyError::InputNotFound(input.to_path_buf()));
+    }
+
+    if !input.is_dir() {
+        let message = match (&cli.bin, &cli.entry) {
+            (Some(_), _) => "--bin requires a crate directory as input",
+            (_, Some(_)) => "--entry requires a directory as input",
+            _ => return Ok(input.to_path_buf()),
+        };
+        return Err(ScriptifyError::Usage(message.to_string()));
+    }
+
+    if let Some(entry) = &cli.entry {
+        return resolve_entry(input, entry);
+    }
+
+    // If input is a directory, find Cargo.toml and determine entry point
+    let manifest_path = input.join("Cargo.toml");
+    if !manifest_path.exists() {
+        return Err(ScriptifyError::ManifestError(format!(
+            "No Cargo.toml found in directory: {}",
+            input.display()
+        )));
+    }
+
+    let manifest_content = read_manifest(&manifest_path)?;
+    let entry_point =
+        parse_entry_point(&manifest_content, input, cli.bin.as_deref(), !cli.no_ignore)?;
+
+    Ok(entry_point)
+}
+
+/// The `--entry` file, relative to the input directory unless absolute
+fn resolve_entry(base_dir: &Path, entry: &Path) -> Result<PathBuf> {
+    let path = base_dir.join(entry);
+
+    if !path.is_file() {
+        return Err(ScriptifyError::InputNotFound(path));
+    }
+    if path.extension().is_none_or(|ext| ext != "rs") {
+        return Err(ScriptifyError::Usage(format!(
+            "--entry must be a Rust source file, got '{}'",
+            path.display()
+        )));
+    }
+
+    Ok(path)
+}
+
+fn parse_entry_point(
+    manifest_content: &str,
+    base_dir: &Path,
+    bin: Option<&str>,
+    respect_ignore: bool,
+) -> Result<PathBuf> {
+    let manifest: toml::Value = toml::from_str(manifest_content)?;
+
+    if let Some(name) = bin {
+        return select_bin(&manifest, base_dir, name, respect_ignore);
+    }
+
+    // Check for [[bin]] entries first
+    if let Some(bins) = manifest.get("bin").and_then(|b| b.as_array())
+        && let Some(first_bin) = bins.first()
+        && let Some(path) = first_bin.get("path").and_then(|p| p.as_str())
+    {
+        return Ok(base_dir.join(path));
+    }
+
+    // Check for single [bin] entry
+    if let Some(bin) = manifest.get("bin").and_then(|b| b.as_table())
+        && let Some(path) = bin.get("path").and_then(|p| p.as_str())
+    {
+        return Ok(base_dir.join(path));
+    }
+
+    // Check for lib
+    if let Some(lib) = manifest.get("lib") {
+        if let Some(path) = lib.get("path").and_then(|p| p.as_str()) {
+            return Ok(base_dir.join(path));
+        }
+        // Default lib path
+        let lib_path = base_dir.join("src/lib.rs");
+        if lib_path.exists() {
+            return Ok(lib_path);
+        }
+    }
+
+    // Default: check for src/main.rs (binary crate default)
+    let main_path = base_dir.join("src/main.rs");
+    if main_path.exists() {
+        return Ok(main_path);
+    }
+
+    // Fallback: check for src/lib.rs
+    let lib_path = base_dir.join("src/lib.rs");
+    if lib_path.exists() {
+        return Ok(lib_path);
+    }
+
+    // Last resort: binaries discovered by convention under src/bin/
+    let bins = discover_bins(&manifest, base_dir, respect_ignore);
+    match bins.as_slice() {
+        [only] => Ok(only.path.clone()),
+        [] => Err(ScriptifyError::ManifestError(
+            "Could not determine entry point from Cargo.toml. No src/main.rs or src/lib.rs found."
+                .to_string(),
+        )),
+        _ => Err(ScriptifyError::ManifestError(format!(
+            "multiple binaries found ({}); select one with --bin",
+            bin_names(&bins)
+        ))),
+    }
+}
+
+/// A named binary or example target and its entry file
+struct Target {
+    name: String,
+    path: PathBuf,
+}
+
+fn select_bin(
+    manifest: &toml::Value,
+    base_dir: &Path,
+    name: &str,
+    respect_ignore: bool,
+) -> Result<PathBuf> {
+    let bins = discover_bins(manifest, base_dir, respect_ignore);
+
+    bins.iter()
+        .find(|b| b.name == name)
+        .map(|b| b.path.clone())
+        .ok_or_else(|| {
+            ScriptifyError::ManifestError(format!(
+                "no binary named '{name}' (available: {})",
+                bin_names(&bins)
+            ))
+        })
+}
+
+fn bin_names(bins: &[Target]) -> String {
+    let names: Vec<&str> = bins.iter().map(|b| b.name.as_str()).collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Collects explicit `[[bin]]` tables plus the binaries cargo discovers by convention
+/// (`src/main.rs`, `src/bin/*.rs` and `src/bin/*/main.rs`) unless `autobins = false`.
+/// Convention binaries excluded by `.gitignore` are skipped when `respect_ignore` is set.
+fn discover_bins(manifest: &toml::Value, base_dir: &Path, respect_ignore: bool) -> Vec<Target> {
+    let package = manifest.get("package");
+    let package_name = package.and_then(|p| p.get("name")).and_then(|n| n.as_str());
+
+    let explicit = match manifest.get("bin") {
+        Some(toml::Value::Array(tables)) => tables.iter().collect(),
+        Some(table @ toml::Value::Table(_)) => vec![table],
+        _ => Vec::new(),
+    };
+
+    let mut bins: Vec<Target> = explicit
+        .into_iter()
+        .filter_map(|table| {
+            let path = table.get("path").and_then(|p| p.as_str());
+            let name = table
+                .get("name")
+                .and_then(|n| n.as_str())
+                .or_else(|| Path::new(path?).file_stem()?.to_str())?;
+            let path = match path {
+                Some(path) => base_dir.join(path),
+                None => default_bin_path(base_dir, name, package_name),
+            };
+            Some(Target {
+                name: name.to_string(),
+                path,
+            })
+        })
+        .collect();
+
+    let autobins = package
+        .and_then(|p| p.get("autobins"))
+        .and_then(|a| a.as_bool())
+        .unwrap_or(true);
+
+    if autobins {
+        for target in convention_bins(base_dir, package_name, respect_ignore) {
+            if !bins
+                .iter()
+                .any(|b| b.name == target.name || b.path == target.path)
+            {
+                bins.push(target);
+            }
+        }
+    }
+
+    bins
+}
+
+/// Collects explicit `[[example]]` tables plus `examples/*.rs` and `examples/*/main.rs`
+/// unless `autoexamples = false`
+fn discover_examples(manifest: &toml::Value, base_dir: &Path, respect_ignore: bool) -> Vec<Target> {
+    let examples_dir = base_dir.join("examples");
+    let explicit: Vec<&toml::Value> = manifest
+        .get("example")
+        .and_then(|e| e.as_array())
+        .map(|tables| tables.iter().collect())
+        .unwrap_or_default();
+
+    let mut examples: Vec<Target> = explicit
+        .into_iter()
+        .filter_map(|table| {
+            let name = table.get("name").and_then(|n| n.as_str())?;
+            let path = match table.get("path").and_then(|p| p.as_str()) {
+                Some(path) => base_dir.join(path),
+                None => examples_dir.join(format!("{name}.rs")),
+            };
+            Some(Target {
+                name: name.to_string(),
+                path,
+            })
+        })
+        .collect();
+
+    let autoexamples = manifest
+        .get("package")
+        .and_then(|p| p.get("autoexamples"))
+        .and_then(|a| a.as_bool())
+        .unwrap_or(true);
+
+    if autoexamples {
+        for target in scan_targets(&examples_dir, respect_ignore) {
+            if !examples
+                .iter()
+                .any(|e| e.name == target.name || e.path == target.path)
+            {
+                examples.push(target);
+            }
+        }
+    }
+
+    examples
+}
+
+/// The library target's name and entry file, if the crate has one
+fn discover_lib(manifest: &toml::Value, base_dir: &Path) -> Option<Target> {
+    let lib = manifest.get("lib");
+    let path = match lib.and_then(|l| l.get("path")).and_then(|p| p.as_str()) {
+        Some(path) => base_dir.join(path),
+        None => Some(base_dir.join("src/lib.rs")).filter(|p| p.exists())?,
+    };
+    let name = lib
+        .and_then(|l| l.get("name"))
+        .or_else(|| manifest.get("package")?.get("name"))
+        .and_then(|n| n.as_str())
+        .unwrap_or_default()
+        .replace('-', "_");
+
+    Some(Target { name, path })
+}
+
+fn convention_bins(
+    base_dir: &Path,
+    package_name: Option<&str>,
+    respect_ignore: bool,
+) -> Vec<Target> {
+    let mut bins = Vec::new();
+
+    let main_path = base_dir.join("src/main.rs");
+    if let Some(name) = package_name
+        && main_path.exists()
+    {
+        bins.push(Target {
+            name: name.to_string(),
+            path: main_path,
+        });
+    }
+
+    bins.extend(scan_targets(&base_dir.join("src/bin"), respect_ignore));
+    bins
+}
+
+/// Targets laid out by cargo's conventions in `dir` (`<name>.rs` and `<name>/main.rs`), by name
+fn scan_targets(dir: &Path, respect_ignore: bool) -> Vec<Target> {
+    let mut discovered: Vec<Target> = glob::walker(dir, respect_ignore)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.depth() == 1)
+        .filter_map(|entry| {
+            let path = entry.path().to_path_buf();
+            if path.is_dir() {
+                let main_path = path.join("main.rs");
+                return main_path.exists().then(|| Target {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    path: main_path,
+                });
+            }
+
+            let is_rust = path.extension().is_some_and(|ext| ext == "rs");
+            let name = path.file_stem()?.to_string_lossy().into_owned();
+            is_rust.then_some(Target { name, path })
+        })
+        .collect();
+
+    discovered.sort_by(|a, b| a.name.cmp(&b.name));
+    discovered
+}
+
+fn default_bin_path(base_dir: &Path, name: &str, package_name: Option<&str>) -> PathBuf {
+    let main_path = base_dir.join("src/main.rs");
+    if package_name == Some(name) && main_path.exists() {
+        return main_path;
+    }
+
+    let file_path = base_dir.join("src/bin").join(format!("{name}.rs"));
+    if file_path.exists() {
+        return file_path;
+    }
+
+    base_dir.join("src/bin").join(name).join("main.rs")
+}
+
+/// Prints the targets detected in a crate directory and the entry point scriptify would use
+fn list_targets(cli: &Cli, input: &Path) -> Result<ExitCode> {
+    let manifest_path = input.join("Cargo.toml");
+    if !input.is_dir() || !manifest_path.exists() {
+        return Err(ScriptifyError::Usage(
+            "--list-targets requires a crate directory as input".to_string(),
+        ));
+    }
+
+    let manifest_content = read_manifest(&manifest_path)?;
+    let manifest: toml::Value = toml::from_str(&manifest_content)?;
+    let respect_ignore = !cli.no_ignore;
+
+    let mut targets: Vec<(&str, Target)> = Vec::new();
+    targets.extend(
+        discover_bins(&manifest, input, respect_ignore)
+            .into_iter()
+            .map(|t| ("bin", t)),
+    );
+    targets.extend(discover_lib(&manifest, input).map(|t| ("lib", t)));
+    targets.extend(
+        discover_examples(&manifest, input, respect_ignore)
+            .into_iter()
+            .map(|t| ("example", t)),
+    );
+
+    let default = parse_entry_point(&manifest_content, input, None, respect_ignore);
+    let relative = |path: &Path| {
+        path.strip_prefix(input)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    match cli.format {
+        ListFormat::Human => {
+            let name_width = targets.iter().map(|(_, t)| t.name.len()).max().unwrap_or(0);
+            for (kind, target) in &targets {
+                println!(
+                    "{kind:<7}  {:<name_width$}  {}",
+                    target.name,
+                    relative(&target.path)
+                );
+            }
+            match &default {
+                Ok(path) => println!("\ndefault: {}", relative(path)),
+                Err(e) => println!("\ndefault: none ({e})"),
+            }
+        }
+        ListFormat::Json => {
+            let entries: Vec<String> = targets
+                .iter()
+                .map(|(kind, target)| {
+                    format!(
+                        "{{\"kind\":{},\"name\":{},\"path\":{}}}",
+                        diagnostics::json_string(kind),
+                        diagnostics::json_string(&target.name),
+                        diagnostics::json_string(&relative(&target.path))
+                    )
+                })
+                .collect();
+            let default = match &default {
+                Ok(path) => diagnostics::json_string(&relative(path)),
+                Err(_) => "null".to_string(),
+            };
+            println!(
+                "{{\"targets\":[{}],\"default\":{default}}}",
+                entries.join(",")
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// --dump-ast: prints the inlined and transformed `syn` tree to stderr. On a terminal, a
+/// dump longer than `DUMP_AST_MAX_LINES` is cut off; redirect stderr for all of it.
+fn dump_ast(cli: &Cli, input_path: &Path) -> Result<ExitCode> {
+    if is_stdin(input_path) || is_url(input_path) {
+        return Err(ScriptifyError::Usage(
+            "--dump-ast requires a file or crate directory as input".to_string(),
+        ));
+    }
+
+    let input = resolve_input_path(cli, input_path)?;
+    let (file, errors, _) = inline_ast(cli, &input)?;
+    for error in &errors {
+        diagnostics::warn_at(Some(error.location()), error);
+    }
+    let file = transform_inlined(cli, file)?;
+
+    let dump = format!("{file:#?}");
+    let total = dump.lines().count();
+    if total > DUMP_AST_MAX_LINES && std::io::stderr().is_terminal() {
+        for line in dump.lines().take(DUMP_AST_MAX_LINES) {
+            eprintln!("{line}");
+        }
+        diagnostics::warn(format!(
+            "--dump-ast: showing {DUMP_AST_MAX_LINES} of {total} lines ({} KiB); redirect stderr to a file for the whole tree, or narrow it with --only-module",
+            dump.len() / 1024
+        ));
+    } else {
+        eprintln!("{dump}");
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Resolves the modules of the input without formatting anything and prints the entry
+/// point with the module files below it, nested as the modules are
+fn list_inlined(cli: &Cli, input_path: &Path) -> Result<ExitCode> {
+    if is_stdin(input_path) || is_url(input_path) {
+        return Err(ScriptifyError::Usage(
+            "--list-inlined requires a file or crate directory as input".to_string(),
+        ));
+    }
+    if cli.inline_path_deps {
+        diagnostics::warn("--list-inlined doesn't list the files of inlined path dependencies");
+    }
+
+    let input = resolve_input_path(cli, input_path)?;
+    let mut source = encoding::read_to_string(&input, cli.input_encoding)?;
+    if cli.reuse_frontmatter {
+        source = take_embedded_manifest(source).0;
+    }
+    // Only the files matter, so nothing is embedded or annotated
+    let options = inline::InlineOptions {
+        preserve_comments: false,
+        embed_assets: false,
+        module_banner: None,
+        ..inline_options(cli)
+    };
+    let (files, errors) = inline::resolve_files(&source, &input, &options)?;
+    for error in &errors {
+        diagnostics::warn_at(Some(error.location()), error);
+    }
+
+    // Each file's depth is the number of listed modules it is nested in
+    let mut ancestors: Vec<&[String]> = Vec::new();
+    let depths: Vec<usize> = files
+        .iter()
+        .map(|file| {
+            while ancestors
+                .last()
+                .is_some_and(|parent| !file.module_path.starts_with(parent))
+            {
+                ancestors.pop();
+            }
+            ancestors.push(&file.module_path);
+            ancestors.len()
+        })
+        .collect();
+
+    match cli.format {
+        ListFormat::Human => {
+            println!("{}", input.display());
+            for (file, depth) in files.iter().zip(&depths) {
+                println!(
+                    "{}{}  {}",
+                    "  ".repeat(*depth),
+                    file.module_path.join("::"),
+                    file.path.display()
+                );
+            }
+        }
+        ListFormat::Json => {
+            // Opens a `modules` array after each file and closes it when the next file is
+            // no deeper
+            let mut json = format!(
+                "{{\"module\":\"crate\",\"path\":{},\"modules\":[",
+                diagnostics::json_string(&input.display().to_string())
+            );
+            let mut previous = 0;
+            for (file, &depth) in files.iter().zip(&depths) {
+                if depth <= previous {
+                    json.push_str(&"]}".repeat(previous - depth + 1));
+                    json.push(',');
+                }
+                json.push_str(&format!(
+                    "{{\"module\":{},\"path\":{},\"modules\":[",
+                    diagnostics::json_string(&file.module_path.join("::")),
+                    diagnostics::json_string(&file.path.display().to_string())
+                ));
+                previous = depth;
+            }
+            json.push_str(&"]}".repeat(previous + 1));
+            println!("{json}");
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the crates found by the dependency scan as sorted JSON, each with whether the
+/// resolved manifest declares it
+fn emit_deps_json(cli: &Cli, input: &Path) -> Result<ExitCode> {
+    let (code, manifest) = generate_code(cli, input)?;
+    let file = syn::parse_file(&code).map_err(|e| inline::parse_error(input, &e))?;
+
+    let declared = match manifest_content(&manifest, &script_style(cli))? {
+        Some(content) => manifest_dependencies(&content)?,
+        None => {
+            diagnostics::warn("no manifest to check dependencies against (use -m or -z)");
+            HashSet::new()
+        }
+    };
+
+    let entries: Vec<String> = deps::external_crates(&file)
+        .iter()
+        .map(|name| {
+            format!(
+                "{{\"name\":{},\"in_manifest\":{}}}",
+                diagnostics::json_string(name),
+                declared.contains(name)
+            )
+        })
+        .collect();
+    println!("[{}]", entries.join(","));
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Names of the `[dependencies]` (including target-specific ones) of a manifest, as
+/// written in code, i.e. with `-` replaced by `_`
+fn manifest_dependencies(manifest_content: &str) -> Result<HashSet<String>> {
+    let manifest: toml::Value = toml::from_str(manifest_content)?;
+    Ok(dependency_tables(&manifest, &["dependencies"])
+        .flat_map(|(_, deps)| deps.keys())
+        .map(|name| name.replace('-', "_"))
+        .collect())
+}
+
+/// The `kinds` tables (e.g. `dependencies`) of a manifest, top-level and under
+/// `[target.'cfg(..)']`, each with its kind
+fn dependency_tables<'a>(
+    manifest: &'a toml::Value,
+    kinds: &'a [&'a str],
+) -> impl Iterator<Item = (&'a str, &'a toml::Table)> {
+    let targets = manifest
+        .get("target")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(|targets| targets.values());
+
+    std::iter::once(manifest)
+        .chain(targets)
+        .flat_map(move |table| {
+            kinds
+                .iter()
+                .filter_map(move |&kind| Some((kind, table.get(kind)?.as_table()?)))
+        })
+}
+
+/// Reports dependencies fetched from git or from a registry other than crates.io, and
+/// fails under --deny-network-deps
+fn check_network_deps(cli: &Cli, manifest: &ManifestOption) -> Result<()> {
+    let Some(content) = manifest_content(manifest, &script_style(cli))? else {
+        return Ok(());
+    };
+    let manifest: toml::Value = toml::from_str(&content)?;
+
+    let mut offending = 0;
+    let kinds = ["dependencies", "dev-dependencies", "build-dependencies"];
+    for (kind, deps) in dependency_tables(&manifest, &kinds) {
+        for (name, dep) in deps {
+            let source = if let Some(git) = dep.get("git").and_then(toml::Value::as_str) {
+                format!("git repository '{git}'")
+            } else if let Some(registry) = dep
+                .get("registry")
+                .and_then(toml::Value::as_str)
+                .filter(|registry| *registry != "crates-io")
+            {
+                format!("registry '{registry}'")
+            } else if let Some(index) = dep.get("registry-index").and_then(toml::Value::as_str) {
+                format!("registry index '{index}'")
+            } else {
+                continue;
+            };
+
+            offending += 1;
+            let message = format!("`{name}` in [{kind}] comes from {source}, not crates.io");
+            if cli.deny_network_deps {
+                diagnostics::error(None, message);
+            } else {
+                diagnostics::warn(message);
+            }
+        }
+    }
+
+    if offending > 0 && cli.deny_network_deps {
+        let summary = match offending {
+            1 => "found 1 dependency".to_string(),
+            n => format!("found {n} dependencies"),
+        };
+        return Err(format!("{summary} outside crates.io, denied by --deny-network-deps").into());
+    }
+    Ok(())
+}
+
+/// Warns about each hard-to-read color of the given (possibly comma-separated) themes
+fn check_theme_contrast<'a>(themes: impl Iterator<Item = &'a String>) -> Result<()> {
+    const MAX_CATEGORIES: usize = 3;
+
+    let mut checked = 0;
+    for name in themes.flat_map(|themes| themes.split(',')).map(str::trim) {
+        if name.is_empty() {
+            continue;
+        }
+        let theme = load_theme(name)?;
+        checked += 1;
+
+        for low in contrast::low_contrast_colors(&theme) {
+            let mut categories =
+                low.categories[..low.categories.len().min(MAX_CATEGORIES)].join(", ");
+            if low.categories.len() > MAX_CATEGORIES {
+                categories.push_str(", ...");
+            }
+            diagnostics::warn(format!(
+                "theme '{}': {} on {} has a contrast ratio of {:.1}:1, below {}:1 ({categories})",
+                theme.name,
+                contrast::hex(low.foreground),
+                contrast::hex(low.background),
+                low.ratio,
+                contrast::MIN_RATIO
+            ));
+        }
+    }
+
+    if checked == 0 {
+        diagnostics::warn(
+            "--theme-contrast-check found no theme to check (use --theme or --preview)",
+        );
+    }
+    Ok(())
+}
+
+/// Prints the theme names, each followed by the preview snippet highlighted with it when
+/// previews are wanted
+fn list_themes(cli: &Cli) -> Result<()> {
+    let preview = match &cli.theme_preview_code {
+        _ if cli.plain => None,
+        Some(path) => Some(encoding::read_to_string(path, cli.input_encoding)?),
+        None if use_color(cli.color, &std::io::stdout()) => Some(THEME_PREVIEW.to_string()),
+        None => None,
+    };
+    // Truncated before highlighting, so every theme colors the same lines
+    let preview = preview.map(|code| {
+        let lines: Vec<&str> = code.lines().collect();
+        let shown = lines[..lines.len().min(cli.theme_preview_lines)].join("\n");
+        (shown, lines.len() > cli.theme_preview_lines)
+    });
+
+    println!("Available themes:");
+    for theme in builtin::all() {
+        println!("  {}", theme.name);
+        if let Some((code, truncated)) = &preview {
+            let highlighted = highlight_with(&mut AnsiHighlighter::new(theme), &cli.lang, code);
+            for line in highlighted.lines() {
+                println!("    {line}");
+            }
+            println!("\x1b[0m{}", if *truncated { "    ...\n" } else { "" });
+        }
+    }
+    Ok(())
+}
+
+/// Prints the resolved shebang, where it comes from and a line per part
+fn explain_shebang(cli: &Cli) {
+    let shebang = get_shebang(cli);
+    let source = if cli.shebang.is_some() {
+        "set with --shebang".to_string()
+    } else if std::env::var_os("SCRIPTIFY_SHEBANG").is_some() {
+        "set with SCRIPTIFY_SHEBANG".to_string()
+    } else {
+        let target = value_name(cli.target);
+        match cli.target {
+            ScriptTarget::RustScript => format!("default for --target {target}"),
+            ScriptTarget::CargoScript => format!(
+                "default for --target {target} --toolchain {}",
+                value_name(cli.toolchain)
+            ),
+        }
+    };
+    println!("{shebang}\n({source})\n");
+
+    let parts = shebang::explain(&shebang);
+    let width = parts.iter().map(|part| part.word.len()).max().unwrap_or(0);
+    for part in parts {
+        println!("  {:width$}  {}", part.word, part.meaning);
+    }
+}
+
+/// The command-line spelling of an enum option's value
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+/// Inlined code, plus the manifest to use instead of the resolved one: the input's
+/// embedded manifest under --reuse-frontmatter, or the one --inline-path-deps rewrote
+type Inlined = (String, Option<String>);
+
+fn inline_modules(cli: &Cli, input: &Path) -> Result<Inlined> {
+    let (file, errors, embedded) = inline_ast(cli, input)?;
+    Ok((unparse_inlined(cli, file, &errors)?, embedded))
+}
+
+/// The crate at `input` with its modules (and path dependencies) inlined, before any
+/// transform, along with the modules that couldn't be inlined and the embedded manifest
+fn inline_ast(
+    cli: &Cli,
+    input: &Path,
+) -> Result<(syn::File, Vec<inline::InlineError>, Option<String>)> {
+    let (mut file, mut errors, mut embedded) = if cli.reuse_frontmatter {
+        let source = encoding::read_to_string(input, cli.input_encoding)?;
+        let (source, embedded) = take_embedded_manifest(source);
+        let (file, errors) = timing::time(Phase::Inline, || {
+            inline::inline_source(&source, input, &inline_options(cli))
+        })?;
+        (file, errors, embedded)
+    } else {
+        let (file, errors) = timing::time(Phase::Inline, || {
+            inline::inline_file(input, &inline_options(cli))
+        })?;
+        (file, errors, None)
+    };
+
+    if cli.inline_path_deps {
+        let manifest = match embedded.take() {
+            Some(content) => ManifestOption::Content(content),
+            None => resolve_manifest(cli, input)?,
+        };
+        let (manifest, dep_errors) = timing::time(Phase::Inline, || {
+            inline_path_deps(cli, input, &mut file, manifest)
+        })?;
+        errors.extend(dep_errors);
+        embedded = manifest;
+    }
+    Ok((file, errors, embedded))
+}
+
+/// --inline-path-deps: nests the library of each `path` dependency `file` uses in it and
+/// returns the manifest with those dependencies replaced by their own
+fn inline_path_deps(
+    cli: &Cli,
+    input: &Path,
+    file: &mut syn::File,
+    manifest: ManifestOption,
+) -> Result<(Option<String>, Vec<inline::InlineError>)> {
+    let (content, manifest_dir) = match &manifest {
+        ManifestOption::Path(path) => (
+            read_manifest(path)?,
+            path.parent().unwrap_or(Path::new("")).to_path_buf(),
+        ),
+        ManifestOption::Content(content) => (
+            content.clone(),
+            input.parent().unwrap_or(Path::new("")).to_path_buf(),
+        ),
+        ManifestOption::Empty | ManifestOption::None => {
+            diagnostics::warn(
+                "--inline-path-deps has no manifest to find path dependencies in (use -m or -z)",
+            );
+            return Ok((None, Vec::new()));
+        }
+    };
+
+    let mut table: toml::Table = toml::from_str(&content)?;
+    let used = deps::external_crates(file);
+    let mut errors = Vec::new();
+    let mut inlined = Vec::new();
+    for dep in path_deps::path_dependencies(&table, &manifest_dir) {
+        let name = dep.crate_name();
+        if !used.contains(&name) {
+            continue;
+        }
+
+        let (entry, dep_manifest) = path_deps::library_entry(&dep.dir)?;
+        let (library, library_errors) = inline::inline_file(&entry, &inline_options(cli))?;
+        errors.extend(library_errors);
+        for warning in path_deps::nest_crate(file, &name, library) {
+            diagnostics::warn(format!("--inline-path-deps: {warning}"));
+        }
+        merge_dependencies(&mut table, &dep.key, &dep_manifest, &dep.dir);
+        inlined.push(dep.key);
+    }
+
+    if inlined.is_empty() {
+        return Ok((
+            matches!(manifest, ManifestOption::Content(_)).then_some(content),
+            errors,
+        ));
+    }
+    diagnostics::note(format!(
+        "--inline-path-deps inlined {} crate(s): {}",
+        inlined.len(),
+        inlined.join(", ")
+    ));
+    let content = toml::to_string(&table)
+        .map_err(|e| ScriptifyError::ManifestError(format!("failed to write manifest: {e}")))?;
+    Ok((Some(content), errors))
+}
+
+/// Replaces the dependency `key` of `manifest` with the `[dependencies]` of the inlined
+/// crate's manifest, keeping entries the manifest already has. The inlined crate's
+/// relative `path`s are resolved against `dir`, since they won't be next to it anymore.
+fn merge_dependencies(manifest: &mut toml::Table, key: &str, inlined: &toml::Table, dir: &Path) {
+    let Some(deps) = manifest
+        .get_mut("dependencies")
+        .and_then(toml::Value::as_table_mut)
+    else {
+        return;
+    };
+    deps.remove(key);
+
+    let inherited = inlined
+        .get("dependencies")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flatten();
+    for (name, dep) in inherited {
+        if let Some(existing) = deps.get(name) {
+            if existing != dep {
+                diagnostics::warn(format!(
+                    "--inline-path-deps: `{key}` depends on `{name}` differently than the manifest, keeping the manifest's"
+                ));
+            }
+            continue;
+        }
+
+        let mut dep = dep.clone();
+        if let Some(path) = dep.get("path").and_then(toml::Value::as_str)
+            && Path::new(path).is_relative()
+        {
+            let path = workspace::path_value(&dir.join(path));
+            dep.as_table_mut()
+                .expect("has a path")
+                .insert("path".to_string(), path);
+            diagnostics::warn(format!(
+                "--inline-path-deps: `{name}` is a path dependency of `{key}` and stays a dependency (only one level is inlined)"
+            ));
+        }
+        if dep.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+            diagnostics::warn(format!(
+                "--inline-path-deps: `{key}` inherits `{name}` from its workspace, use --path-deps to resolve it"
+            ));
+        }
+        deps.insert(name.clone(), dep);
+    }
+}
+
+fn inline_stdin(cli: &Cli, logical_path: &Path) -> Result<Inlined> {
+    let mut bytes = Vec::new();
+    timing::time(Phase::Input, || std::io::stdin().read_to_end(&mut bytes))
+        .map_err(ScriptifyError::io("failed to read stdin"))?;
+    let mut source = encoding::decode(bytes, cli.input_encoding)
+        .map_err(|reason| format!("stdin is not valid {}: {reason}", cli.input_encoding))?;
+    let mut embedded = None;
+    if cli.reuse_frontmatter {
+        (source, embedded) = take_embedded_manifest(source);
+    }
+    let (file, errors) = timing::time(Phase::Inline, || {
+        inline::inline_source(&source, logical_path, &inline_options(cli))
+    })?;
+    Ok((unparse_inlined(cli, file, &errors)?, embedded))
+}
+
+/// Fetches a URL input and processes it as a single file, since `mod foo;` declarations
+/// have no filesystem to be resolved against
+fn inline_url(cli: &Cli, url: &Path) -> Result<Inlined> {
+    if !cli.allow_net {
+        return Err(ScriptifyError::Usage(format!(
+            "fetching '{}' requires --allow-net",
+            url.display()
+        )));
+    }
+
+    let mut source = timing::time(Phase::Input, || fetch::fetch(&url.to_string_lossy()))?;
+    let mut embedded = None;
+    if cli.reuse_frontmatter {
+        (source, embedded) = take_embedded_manifest(source);
+    }
+    let mut file = timing::time(Phase::Inline, || syn::parse_file(&source))
+        .map_err(|e| inline::parse_error(url, &e))?;
+    if cli.embed_assets {
+        diagnostics::warn(
+            "--embed-assets has no effect for URL inputs, included files aren't fetched",
+        );
+    }
+    if cli.preserve_mod_comments {
+        comments::attach(&source, &mut file);
+    }
+
+    for item in &file.items {
+        if let syn::Item::Mod(module) = item
+            && module.content.is_none()
+        {
+            diagnostics::warn(format!(
+                "module `{}` left unresolved: submodules aren't fetched for URL inputs",
+                module.ident
+            ));
+        }
+    }
+    Ok((unparse_inlined(cli, file, &[])?, embedded))
+}
+
+fn inline_options(cli: &Cli) -> inline::InlineOptions<'_> {
+    inline::InlineOptions {
+        include_dirs: &cli.include_dir,
+        preserve_comments: cli.preserve_mod_comments,
+        exclude_modules: &cli.exclude_module,
+        module_filter: &cli.module_filter,
+        progress: !cli.quiet && !cli.json_errors && std::io::stderr().is_terminal(),
+        embed_assets: cli.embed_assets,
+        encoding: cli.input_encoding,
+        module_banner: cli.group_by_module.then(|| {
+            cli.module_banner
+                .as_deref()
+                .unwrap_or(DEFAULT_MODULE_BANNER)
+        }),
+        relative_banner_paths: cli.canonical_output,
+    }
+}
+
+fn unparse_inlined(cli: &Cli, file: syn::File, errors: &[inline::InlineError]) -> Result<String> {
+    for error in errors {
+        diagnostics::warn_at(Some(error.location()), error);
+    }
+
+    if is_no_std(&file) {
+        diagnostics::warn(
+            "the crate is `#![no_std]`: the generated script may need a no_std-compatible setup (e.g. `default-features = false` on dependencies, a `#[panic_handler]`) and can otherwise fail to link",
+        );
+    }
+
+    let file = timing::time(Phase::Transforms, || transform_inlined(cli, file))?;
+    if cli.assert_single_file {
+        assert_single_file(&file)?;
+    }
+
+    let formatter = formatter(cli);
+    let code = timing::time(Phase::Format, || formatter.format(&file))?;
+    Ok(timing::time(Phase::Transforms, || {
+        if cli.format_check {
+            check_format_stability(&code, formatter.as_ref());
+        }
+
+        if cli.preserve_mod_comments || cli.resolve_reexports || cli.group_by_module {
+            comments::restore(&code)
+        } else {
+            code
+        }
+    }))
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// The AST passes run on the inlined crate before it is formatted
+fn transform_inlined(cli: &Cli, mut file: syn::File) -> Result<syn::File> {
+    if let Some(module_path) = &cli.only_module {
+        file = extract::extract_module(file, module_path)?;
+        if extract::has_outside_references(&file) {
+            diagnostics::warn(format!(
+                "module `{module_path}` refers to items outside it through `crate::` or `super::` paths, which won't resolve in the extracted file"
+            ));
+        }
+    }
 
-    if cli.list_themes {
-        list_themes();
-        return;
+    if !cli.strip_derive.is_empty() {
+        let stripped = strip_derive::strip_derives(&mut file, &cli.strip_derive);
+        for (name, count) in &stripped.derives {
+            match count {
+                0 => diagnostics::warn(format!("--strip-derive found no `{name}` derive")),
+                n => diagnostics::note(format!("--strip-derive removed {n} `{name}` derive(s)")),
+            }
+        }
+        if !stripped.imports.is_empty() {
+            diagnostics::note(format!(
+                "--strip-derive removed unused import(s): {}",
+                stripped.imports.join(", ")
+            ));
+        }
     }
 
-    let result = run(&cli);
+    if cli.strip_private {
+        let stripped = strip_private::strip_private(&mut file);
+        for (name, user) in &stripped.kept {
+            diagnostics::warn(format!(
+                "--strip-private kept private `{name}` because {user} uses it"
+            ));
+        }
+        if !stripped.removed.is_empty() {
+            diagnostics::note(format!(
+                "--strip-private removed {} private item(s): {}",
+                stripped.removed.len(),
+                stripped.removed.join(", ")
+            ));
+        }
+    }
 
-    if let Err(e) = result {
-        eprintln!("error: {e}");
-        std::process::exit(1);
+    if cli.drop_empty_mods {
+        let dropped = empty_mods::drop_empty_mods(&mut file);
+        for path in &dropped.referenced {
+            diagnostics::warn(format!(
+                "--drop-empty-mods removed `{path}`, but its name is still mentioned elsewhere and may no longer resolve"
+            ));
+        }
+        if !dropped.removed.is_empty() {
+            diagnostics::note(format!(
+                "--drop-empty-mods removed {} empty module(s): {}",
+                dropped.removed.len(),
+                dropped.removed.join(", ")
+            ));
+        }
     }
-}
 
-fn run(cli: &Cli) -> Result<()> {
-    let input_path = cli.input.as_ref().ok_or("<INPUT> is required")?;
-    let input = resolve_input_path(input_path)?;
+    if cli.warn_unsafe || cli.deny_unsafe {
+        report_unsafe(cli, &file)?;
+    }
 
-    let code = inline_modules(&input)?;
-    let manifest = resolve_manifest(cli, &input)?;
-    let output_content = prepare_output(&code, cli.theme.as_deref(), manifest)?;
+    if let Some(limit) = cli.warn_large_functions {
+        for function in fn_size::large_functions(&file, limit) {
+            diagnostics::warn(function);
+        }
+    }
 
-    if let Some(out_path) = &cli.output {
-        std::fs::write(out_path, output_content)
-            .map_err(|e| format!("failed to write '{}': {e}", out_path.display()))?;
-    } else {
-        print!("{output_content}");
+    if cli.resolve_reexports {
+        for chain in reexports::annotate_chains(&mut file) {
+            diagnostics::note(format!("re-export chain: {chain}"));
+        }
     }
 
-    Ok(())
-}
+    if cli.wrap_test_harness && harness::wrap_tests(&mut file) == 0 {
+        diagnostics::warn("--wrap-test-harness found no #[test] functions");
+    }
 
-fn resolve_input_path(input: &Path) -> Result<PathBuf> {
-    if !input.is_dir() {
-        return Ok(input.to_path_buf());
+    if !cli.append_run_args.is_empty()
+        && !run_args::inject_default_args(&mut file, &cli.append_run_args)
+    {
+        diagnostics::warn("--append-run-args has no effect, there is no `fn main` to wrap");
     }
 
-    // If input is a directory, find Cargo.toml and determine entry point
-    let manifest_path = input.join("Cargo.toml");
-    if !manifest_path.exists() {
-        return Err(format!("No Cargo.toml found in directory: {}", input.display()).into());
+    if cli.alphabetize_items
+        && alphabetize::alphabetize(&mut file)
+        && file
+            .items
+            .iter()
+            .any(|item| matches!(item, syn::Item::Macro(_)))
+    {
+        diagnostics::warn(
+            "--alphabetize-items reordered items in a file with macros; items never move across a macro, but macros that depend on item order may still break",
+        );
     }
 
-    let manifest_content = std::fs::read_to_string(&manifest_path)
-        .map_err(|e| format!("failed to read '{}': {e}", manifest_path.display()))?;
-    let entry_point = parse_entry_point(&manifest_content, input)?;
+    Ok(file)
+}
 
-    Ok(entry_point)
+/// Whether the crate root declares `#![no_std]`, directly or through `cfg_attr`
+fn is_no_std(file: &syn::File) -> bool {
+    file.attrs.iter().any(|attr| {
+        let path = attr.path();
+        path.is_ident("no_std")
+            || (path.is_ident("cfg_attr")
+                && attr.meta.require_list().is_ok_and(|list| {
+                    list.tokens
+                        .clone()
+                        .into_iter()
+                        .any(|token| token.to_string() == "no_std")
+                }))
+    })
 }
 
-fn parse_entry_point(manifest_content: &str, base_dir: &Path) -> Result<PathBuf> {
-    let manifest: toml::Value = toml::from_str(manifest_content)?;
+/// Reports every module declaration without a body, failing if there is any
+fn assert_single_file(file: &syn::File) -> Result<()> {
+    fn collect(items: &[syn::Item], path: &mut Vec<String>, unresolved: &mut Vec<String>) {
+        for item in items {
+            let syn::Item::Mod(module) = item else {
+                continue;
+            };
+            path.push(module.ident.to_string());
+            match &module.content {
+                Some((_, items)) => collect(items, path, unresolved),
+                None => unresolved.push(path.join("::")),
+            }
+            path.pop();
+        }
+    }
 
-    // Check for [[bin]] entries first
-    if let Some(bins) = manifest.get("bin").and_then(|b| b.as_array())
-        && let Some(first_bin) = bins.first()
-        && let Some(path) = first_bin.get("path").and_then(|p| p.as_str())
-    {
-        return Ok(base_dir.join(path));
+    let mut unresolved = Vec::new();
+    collect(&file.items, &mut vec!["crate".to_string()], &mut unresolved);
+    if unresolved.is_empty() {
+        return Ok(());
     }
 
-    // Check for single [bin] entry
-    if let Some(bin) = manifest.get("bin").and_then(|b| b.as_table())
-        && let Some(path) = bin.get("path").and_then(|p| p.as_str())
-    {
-        return Ok(base_dir.join(path));
+    for module in &unresolved {
+        diagnostics::error(None, format!("module `{module}` was not inlined"));
     }
+    Err(format!(
+        "{} module declaration(s) left in the output, denied by --assert-single-file",
+        unresolved.len()
+    )
+    .into())
+}
 
-    // Check for lib
-    if let Some(lib) = manifest.get("lib") {
-        if let Some(path) = lib.get("path").and_then(|p| p.as_str()) {
-            return Ok(base_dir.join(path));
-        }
-        // Default lib path
-        let lib_path = base_dir.join("src/lib.rs");
-        if lib_path.exists() {
-            return Ok(lib_path);
-        }
+/// Lists the first few `unsafe` sites and fails under --deny-unsafe
+fn report_unsafe(cli: &Cli, file: &syn::File) -> Result<()> {
+    const MAX_REPORTED: usize = 5;
+
+    let sites = unsafe_scan::find_unsafe(file);
+    if sites.is_empty() {
+        return Ok(());
     }
 
-    // Default: check for src/main.rs (binary crate default)
-    let main_path = base_dir.join("src/main.rs");
-    if main_path.exists() {
-        return Ok(main_path);
+    let report = |site: &unsafe_scan::UnsafeSite| {
+        if cli.deny_unsafe {
+            diagnostics::error(None, site);
+        } else {
+            diagnostics::warn(site);
+        }
+    };
+    sites.iter().take(MAX_REPORTED).for_each(report);
+
+    let summary = match sites.len() {
+        1 => "found 1 use of `unsafe`".to_string(),
+        n if n > MAX_REPORTED => format!(
+            "found {n} uses of `unsafe` ({} more not shown)",
+            n - MAX_REPORTED
+        ),
+        n => format!("found {n} uses of `unsafe`"),
+    };
+    if cli.deny_unsafe {
+        return Err(format!("{summary}, denied by --deny-unsafe").into());
     }
+    diagnostics::warn(summary);
+    Ok(())
+}
 
-    // Fallback: check for src/lib.rs
-    let lib_path = base_dir.join("src/lib.rs");
-    if lib_path.exists() {
-        return Ok(lib_path);
+fn formatter(cli: &Cli) -> Box<dyn Formatter + '_> {
+    match cli.fmt_backend {
+        FmtBackend::Prettyplease => Box::new(formatter::Prettyplease),
+        FmtBackend::Rustfmt => Box::new(formatter::Rustfmt {
+            edition: &cli.edition,
+            keep_order: cli.preserve_item_order,
+        }),
     }
+}
 
-    Err(
-        "Could not determine entry point from Cargo.toml. No src/main.rs or src/lib.rs found."
-            .into(),
-    )
+/// Warns if formatting the formatter's own output changes it again
+fn check_format_stability(code: &str, formatter: &dyn Formatter) {
+    let reformatted = match syn::parse_file(code).map(|file| formatter.format(&file)) {
+        Ok(Ok(reformatted)) => reformatted,
+        Ok(Err(e)) => {
+            diagnostics::warn(format!("--format-check: {e}"));
+            return;
+        }
+        Err(e) => {
+            diagnostics::warn(format!(
+                "--format-check: formatted output does not parse: {e}"
+            ));
+            return;
+        }
+    };
+
+    let first_difference = code
+        .lines()
+        .zip(reformatted.lines())
+        .position(|(a, b)| a != b)
+        .or_else(|| {
+            (code != reformatted).then(|| code.lines().count().min(reformatted.lines().count()))
+        });
+
+    if let Some(line) = first_difference {
+        diagnostics::warn(format!(
+            "--format-check: formatter output is not stable, a second pass changes line {}",
+            line + 1
+        ));
+    }
 }
 
-fn list_themes() {
-    println!("Available themes:");
-    for theme in builtin::all() {
-        println!("  {}", theme.name);
+/// Collapses runs of blank lines into one, except inside multi-line string literals, whose
+/// contents are kept as they are
+fn trim_blank_lines(code: &str) -> String {
+    let literals = multi_line_literals(code);
+    let mut trimmed = String::with_capacity(code.len());
+    let mut previous_blank = false;
+    let mut offset = 0;
+
+    for line in code.lines() {
+        let start = offset;
+        offset += line.len() + 1;
+        if literals
+            .iter()
+            .any(|literal| literal.start < start && start < literal.end)
+        {
+            trimmed.push_str(line);
+            trimmed.push('\n');
+            previous_blank = false;
+            continue;
+        }
+
+        let blank = line.trim().is_empty();
+        if blank && previous_blank {
+            continue;
+        }
+
+        trimmed.push_str(line);
+        trimmed.push('\n');
+        previous_blank = blank;
     }
+
+    trimmed
 }
 
-fn inline_modules(input: &Path) -> Result<String> {
-    let result = InlinerBuilder::default()
-        .parse_and_inline_modules(input)
-        .map_err(|e| format!("failed to inline modules from '{}': {e}", input.display()))?;
+/// Byte ranges of the literals in `code` that span more than one line (none if it doesn't
+/// tokenize, e.g. because it isn't Rust)
+fn multi_line_literals(code: &str) -> Vec<std::ops::Range<usize>> {
+    fn collect(tokens: proc_macro2::TokenStream, ranges: &mut Vec<std::ops::Range<usize>>) {
+        for token in tokens {
+            match token {
+                proc_macro2::TokenTree::Group(group) => collect(group.stream(), ranges),
+                proc_macro2::TokenTree::Literal(literal) if literal.to_string().contains('\n') => {
+                    ranges.push(literal.span().byte_range());
+                }
+                _ => {}
+            }
+        }
+    }
 
-    Ok(prettyplease::unparse(result.output()))
+    let mut ranges = Vec::new();
+    if let Ok(tokens) = code.parse() {
+        collect(tokens, &mut ranges);
+    }
+    ranges
 }
 
 enum ManifestOption {
     Path(PathBuf),
+    /// Manifest text taken from the input itself (--reuse-frontmatter)
+    Content(String),
     Empty,
     None,
 }
@@ -175,11 +3102,25 @@ fn resolve_manifest(cli: &Cli, input: &Path) -> Result<ManifestOption> {
     }
 
     if cli.zscript {
-        let input_abs = input.canonicalize()
-            .map_err(|e| format!("failed to resolve path '{}': {e}", input.display()))?;
-        let search_from = input_abs
-            .parent()
-            .ok_or("Input file has no parent directory")?;
+        // stdin's logical path and URLs need not exist, so they are only made absolute, and
+        // a path that can't be canonicalized (e.g. in a sandbox) falls back to the same
+        let logical = cli.logical_paths
+            || cli
+                .input
+                .as_deref()
+                .is_some_and(|i| is_stdin(i) || is_url(i));
+        let input_abs = if logical {
+            std::path::absolute(input)
+        } else {
+            input.canonicalize().or_else(|_| std::path::absolute(input))
+        }
+        .map_err(ScriptifyError::io(format!(
+            "failed to resolve path '{}'",
+            input.display()
+        )))?;
+        let search_from = input_abs.parent().ok_or_else(|| {
+            ScriptifyError::ManifestError("Input file has no parent directory".to_string())
+        })?;
         let stop_at = cli
             .stop_at_cwd
             .then(|| std::env::current_dir().ok())
@@ -188,6 +3129,18 @@ fn resolve_manifest(cli: &Cli, input: &Path) -> Result<ManifestOption> {
         if let Some(manifest) = find_cargo_toml(search_from, stop_at.as_deref()) {
             return Ok(ManifestOption::Path(manifest));
         }
+
+        if cli.require_manifest {
+            let searched_to = match stop_at.as_deref() {
+                Some(stop) if search_from.starts_with(stop) => stop,
+                _ => search_from.ancestors().last().unwrap_or(search_from),
+            };
+            return Err(ScriptifyError::ManifestError(format!(
+                "--require-manifest: no Cargo.toml found in '{}' or its parent directories up to '{}'",
+                search_from.display(),
+                searched_to.display()
+            )));
+        }
     }
 
     Ok(ManifestOption::None)
@@ -208,82 +3161,904 @@ fn find_cargo_toml(mut current: &Path, stop_at: Option<&Path>) -> Option<PathBuf
     }
 }
 
-fn prepare_output(code: &str, theme: Option<&str>, manifest: ManifestOption) -> Result<String> {
-    let highlighted_code = apply_syntax_highlighting(code, theme)?;
-    format_output(&highlighted_code, manifest)
+/// Wraps `code` into a script and highlights it when `theme` is set, with the
+/// grammar named by `language`
+fn prepare_output(
+    code: &str,
+    (theme, language): (Option<&str>, &str),
+    manifest: ManifestOption,
+    style: &ScriptStyle,
+) -> Result<String> {
+    let output = format_output(code, manifest, style)?;
+    apply_syntax_highlighting(&output, theme, language)
+}
+
+/// Renders `output` once per theme in a comma-separated list, each under a header
+fn compare_themes(output: &str, themes: &str, language: &str) -> Result<String> {
+    let names: Vec<&str> = themes
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+    for name in &names {
+        load_theme(name)?;
+    }
+
+    let mut rendered = String::new();
+    for (i, name) in names.iter().enumerate() {
+        if i > 0 {
+            rendered.push('\n');
+        }
+        rendered.push_str(&format!("==> {name} <==\n"));
+        rendered.push_str(&highlight_output(output, name, language)?);
+    }
+
+    Ok(rendered)
 }
 
-fn apply_syntax_highlighting(code: &str, theme: Option<&str>) -> Result<String> {
+fn apply_syntax_highlighting(output: &str, theme: Option<&str>, language: &str) -> Result<String> {
     match theme {
-        Some(t) => highlight_code(code, t),
-        None => Ok(code.to_string()),
+        Some(t) => highlight_output(output, t, language),
+        None => Ok(output.to_string()),
     }
 }
 
-fn format_output(code: &str, manifest: ManifestOption) -> Result<String> {
-    match manifest {
-        ManifestOption::Path(ref path) => build_cargo_script_with_manifest(path, code),
-        ManifestOption::Empty => Ok(build_cargo_script_empty(code)),
-        ManifestOption::None => Ok(code.to_string()),
+/// How generated code is wrapped into a runnable script
+struct ScriptStyle {
+    /// `None` where --shebang-on leaves it out
+    shebang: Option<String>,
+    target: ScriptTarget,
+    /// Label of the opening `---` fence
+    section: String,
+    omit_empty_deps: bool,
+    minify_manifest: bool,
+    /// Dotted keys and values to set under `[package.metadata]`
+    metadata: Vec<(String, toml::Value)>,
+    /// TOML file merged into `[package.metadata]` before `metadata`
+    metadata_from: Option<PathBuf>,
+}
+
+fn format_output(code: &str, manifest: ManifestOption, style: &ScriptStyle) -> Result<String> {
+    match manifest_content(&manifest, style)? {
+        Some(content) => Ok(assemble_script(code, &content, style)),
+        None => Ok(code.to_string()),
     }
 }
 
+/// The manifest to pair the code with, after --minify-manifest, --omit-empty-deps,
+/// --package-metadata-from and --metadata
+fn manifest_content(manifest: &ManifestOption, style: &ScriptStyle) -> Result<Option<String>> {
+    let content = match manifest {
+        ManifestOption::Path(path) => read_manifest(path)?,
+        ManifestOption::Content(content) => content.clone(),
+        ManifestOption::Empty if style.omit_empty_deps => String::new(),
+        ManifestOption::Empty => "[dependencies]\n".to_string(),
+        ManifestOption::None => return Ok(None),
+    };
+    let content = match manifest {
+        ManifestOption::Path(_) | ManifestOption::Content(_) if style.minify_manifest => {
+            let (minified, removed) = minify::minify_manifest(&content)?;
+            if !removed.is_empty() {
+                diagnostics::note(format!("--minify-manifest removed {}", removed.join(", ")));
+            }
+            minified
+        }
+        _ => content,
+    };
+    let content = match manifest {
+        ManifestOption::Path(_) | ManifestOption::Content(_) if style.omit_empty_deps => {
+            strip_empty_dependencies(&content)
+        }
+        _ => content,
+    };
+    let metadata_file = style
+        .metadata_from
+        .as_deref()
+        .map(read_metadata_file)
+        .transpose()?;
+    add_metadata(&content, metadata_file, &style.metadata).map(Some)
+}
+
 fn read_manifest(manifest: &Path) -> Result<String> {
-    std::fs::read_to_string(manifest)
-        .map_err(|e| format!("failed to read manifest '{}': {e}", manifest.display()).into())
+    std::fs::read_to_string(manifest).map_err(|e| {
+        ScriptifyError::ManifestError(format!(
+            "failed to read manifest '{}': {e}",
+            manifest.display()
+        ))
+    })
+}
+
+/// A builtin theme by name, or the theme in a `.toml` file given by path (--theme-file).
+/// A file with `base = "<builtin>"` is laid over that builtin: its name, variant,
+/// background, foreground and every highlight style the file sets replace the builtin's.
+fn load_theme(name: &str) -> Result<arborium::theme::Theme> {
+    let path = Path::new(name);
+    if path.extension().is_none_or(|ext| ext != "toml") {
+        return find_theme(name);
+    }
+
+    let content = std::fs::read_to_string(path).map_err(ScriptifyError::io(format!(
+        "failed to read theme file '{}'",
+        path.display()
+    )))?;
+    let invalid = |e: &dyn std::fmt::Display| {
+        ScriptifyError::Other(format!("invalid theme file '{}': {e}", path.display()))
+    };
+    let table: toml::Table = toml::from_str(&content).map_err(|e| invalid(&e))?;
+    let mut overlay = arborium::theme::Theme::from_toml(&content).map_err(|e| invalid(&e))?;
+    if !table.contains_key("name") {
+        overlay.name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    }
+
+    let Some(base) = table.get("base") else {
+        return Ok(overlay);
+    };
+    let base = base
+        .as_str()
+        .ok_or_else(|| invalid(&"`base` must be the name of a builtin theme"))?;
+    let mut theme = find_theme(base)?;
+    theme.name = overlay.name;
+    if table.contains_key("variant") {
+        theme.is_dark = overlay.is_dark;
+    }
+    theme.background = overlay.background.or(theme.background);
+    theme.foreground = overlay.foreground.or(theme.foreground);
+    for (style, custom) in theme.styles.iter_mut().zip(overlay.styles) {
+        if !custom.is_empty() {
+            *style = custom;
+        }
+    }
+    Ok(theme)
 }
 
 fn find_theme(name: &str) -> Result<arborium::theme::Theme> {
-    builtin::all()
+    let themes = builtin::all();
+    let suggestion = themes
+        .iter()
+        .map(|t| {
+            (
+                strsim::jaro_winkler(&t.name.to_lowercase(), &name.to_lowercase()),
+                t,
+            )
+        })
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, t)| t.name.clone());
+
+    themes
         .into_iter()
         .find(|t| t.name.eq_ignore_ascii_case(name))
-        .ok_or_else(|| {
-            format!("unknown theme '{name}'. Use --list-themes to see available themes").into()
+        .ok_or_else(|| ScriptifyError::ThemeNotFound {
+            name: name.to_string(),
+            suggestion,
         })
 }
 
-fn highlight_code(code: &str, theme_name: &str) -> Result<String> {
-    let theme = find_theme(theme_name)?;
+/// Highlights the generated output, coloring the embedded manifest as TOML and the rest
+/// with the `language` grammar (normally Rust)
+fn highlight_output(output: &str, theme_name: &str, language: &str) -> Result<String> {
+    let theme = load_theme(theme_name)?;
     let mut highlighter = AnsiHighlighter::new(theme);
-    Ok(highlighter
-        .highlight("rust", code)
-        .unwrap_or_else(|_| code.to_string()))
+    if let Err(arborium::Error::UnsupportedLanguage { .. }) = highlighter.highlight(language, "") {
+        return Err(ScriptifyError::Usage(format!(
+            "unsupported --lang '{language}': this build has no grammar for it"
+        )));
+    }
+
+    let Some((header, manifest, code)) = split_frontmatter(output) else {
+        return Ok(highlight_with(&mut highlighter, language, output));
+    };
+
+    let (fence, code) = code.split_at(FRONTMATTER_CLOSE.len());
+    let mut highlighted = String::with_capacity(output.len());
+
+    highlighted.push_str(header);
+    highlighted.push_str(&highlight_with(&mut highlighter, "toml", manifest));
+    highlighted.push_str(fence);
+    highlighted.push_str(&highlight_with(&mut highlighter, language, code));
+
+    Ok(highlighted)
+}
+
+/// Falls back to plain text when the grammar isn't compiled in or highlighting fails
+fn highlight_with(highlighter: &mut AnsiHighlighter, language: &str, source: &str) -> String {
+    highlighter
+        .highlight(language, source)
+        .unwrap_or_else(|_| source.to_string())
+}
+
+/// Removes an embedded manifest (`---cargo` frontmatter, or a `//! ```cargo` block among
+/// the leading doc comments) and the shebang from a script's source. The removed lines
+/// are left blank so that line numbers in messages still match the input.
+fn take_embedded_manifest(source: String) -> (String, Option<String>) {
+    let mut lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let mut start = 0;
+    if lines
+        .first()
+        .is_some_and(|line| line.starts_with("#!") && !line.starts_with("#!["))
+    {
+        start = 1;
+    }
+    while lines.get(start).is_some_and(|line| line.trim().is_empty()) {
+        start += 1;
+    }
+
+    let block = &lines[start..];
+    let Some((open, close, is_doc)) = frontmatter_bounds(block)
+        .map(|(open, close)| (open, close, false))
+        .or_else(|| doc_manifest_bounds(block).map(|(open, close)| (open, close, true)))
+    else {
+        return (source, None);
+    };
+    let (open, close) = (start + open, start + close);
+
+    let manifest = lines[open + 1..close]
+        .iter()
+        .map(|line| match line.strip_prefix("//!") {
+            Some(doc) if is_doc => doc.strip_prefix(' ').unwrap_or(doc),
+            _ => line,
+        })
+        .collect::<String>();
+    for i in (0..start).chain(open..=close) {
+        lines[i] = if lines[i].ends_with('\n') { "\n" } else { "" };
+    }
+
+    (lines.concat(), Some(manifest))
+}
+
+/// Line indices of the `---cargo` (or bare or otherwise labelled `---`) fences, if
+/// `lines` starts with them
+fn frontmatter_bounds(lines: &[&str]) -> Option<(usize, usize)> {
+    let label = lines.first()?.trim_end().strip_prefix(FRONTMATTER_FENCE)?;
+    if !label.is_empty() && parse_section_label(label).is_err() {
+        return None;
+    }
+    let close = lines[1..]
+        .iter()
+        .position(|line| line.trim_end() == FRONTMATTER_CLOSE.trim_end())?;
+    Some((0, close + 1))
+}
+
+/// Line indices of the `//! ```cargo` fences within the leading `//!` comments of `lines`
+fn doc_manifest_bounds(lines: &[&str]) -> Option<(usize, usize)> {
+    let doc_lines = lines
+        .iter()
+        .take_while(|line| line.starts_with("//!"))
+        .count();
+    let doc_block = &lines[..doc_lines];
+    let open = doc_block
+        .iter()
+        .position(|line| line.trim_end() == DOC_MANIFEST_OPEN.trim_end())?;
+    let close = doc_block[open + 1..]
+        .iter()
+        .position(|line| line.trim_end() == DOC_MANIFEST_CLOSE.trim_end())?;
+    Some((open, open + 1 + close))
+}
+
+/// Splits generated output into `(header, manifest, rest)`, where `header` ends with the
+/// opening fence (the first `---` line with a label) and `rest` starts with the closing fence
+fn split_frontmatter(output: &str) -> Option<(&str, &str, &str)> {
+    let mut offset = 0;
+    let manifest_start = output.split_inclusive('\n').find_map(|line| {
+        offset += line.len();
+        let label = line.trim_end().strip_prefix(FRONTMATTER_FENCE)?;
+        parse_section_label(label).is_ok().then_some(offset)
+    })?;
+    let manifest_len = output[manifest_start..]
+        .lines()
+        .take_while(|line| *line != FRONTMATTER_CLOSE.trim_end())
+        .map(|line| line.len() + 1)
+        .sum::<usize>();
+    let manifest_end = manifest_start + manifest_len;
+
+    output[manifest_end..]
+        .starts_with(FRONTMATTER_CLOSE)
+        .then(|| {
+            (
+                &output[..manifest_start],
+                &output[manifest_start..manifest_end],
+                &output[manifest_end..],
+            )
+        })
+}
+
+/// Whether --shebang-on allows a shebang for where the output goes
+fn wants_shebang(cli: &Cli) -> bool {
+    // The wrapper runs the command itself
+    if cli.emit_wrapper {
+        return false;
+    }
+    match cli.shebang_on {
+        ShebangOn::File => cli.output.is_some() || cli.output_dir.is_some(),
+        ShebangOn::Always => true,
+        ShebangOn::Never => false,
+    }
+}
+
+fn get_shebang(cli: &Cli) -> String {
+    if let Some(shebang) = &cli.shebang {
+        return shebang.clone();
+    }
+
+    std::env::var("SCRIPTIFY_SHEBANG").unwrap_or_else(|_| {
+        match (cli.target, cli.toolchain) {
+            (ScriptTarget::RustScript, _) => RUST_SCRIPT_SHEBANG,
+            (ScriptTarget::CargoScript, Toolchain::Stable) => DEFAULT_SHEBANG,
+            (ScriptTarget::CargoScript, Toolchain::Nightly) => NIGHTLY_SHEBANG,
+        }
+        .to_string()
+    })
+}
+
+/// Warns (once) if the shebang line is longer than the kernel reads: Linux before 5.1
+/// reads 128 bytes including the newline and silently cuts the rest off, which shows up
+/// as a confusing "bad interpreter" error or truncated arguments
+fn check_shebang_length(shebang: &str) {
+    #[cfg(target_os = "linux")]
+    const LIMIT: Option<(&str, usize)> = Some(("Linux", 127));
+    #[cfg(target_os = "macos")]
+    const LIMIT: Option<(&str, usize)> = Some(("macOS", 511));
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    const LIMIT: Option<(&str, usize)> = None;
+    static CHECKED: std::sync::Once = std::sync::Once::new();
+
+    let Some((platform, limit)) = LIMIT else {
+        return;
+    };
+    if shebang.len() > limit {
+        CHECKED.call_once(|| {
+            diagnostics::warn(format!(
+                "the shebang is {} bytes long, over the {limit} bytes {platform} reads, so it may be cut off when the script is executed; use a shorter --shebang or run the script through a wrapper",
+                shebang.len()
+            ));
+        });
+    }
+}
+
+/// Parses a `--metadata` argument into its dotted key and TOML value
+fn parse_metadata_entry(arg: &str) -> std::result::Result<(String, toml::Value), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{arg}'"))?;
+    let key = key.trim();
+
+    let valid_segment = |segment: &str| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    if !key.split('.').all(valid_segment) {
+        return Err(format!(
+            "invalid key '{key}': use dot-separated segments of letters, digits, '_' and '-'"
+        ));
+    }
+
+    let value = template::expand_env(value)?;
+    let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or(toml::Value::String(value));
+
+    Ok((key.to_string(), value))
+}
+
+/// Parses an octal --output-permissions mode such as `755`, `0755` or `0o755`
+fn parse_mode(mode: &str) -> std::result::Result<u32, String> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    if digits.is_empty() || !digits.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return Err(format!(
+            "invalid mode '{mode}': expected octal digits, e.g. 0755"
+        ));
+    }
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+        .ok_or_else(|| format!("invalid mode '{mode}': the largest mode is 7777"))
+}
+
+/// Validates a --manifest-section label: a letter or '_', then letters, digits, '_' and '-'
+fn parse_section_label(label: &str) -> std::result::Result<String, String> {
+    let mut chars = label.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!(
+            "invalid section label '{label}': use a letter or '_' followed by letters, digits, '_' and '-'"
+        ));
+    }
+    Ok(label.to_string())
+}
+
+fn parse_banner(template: &str) -> std::result::Result<String, String> {
+    let template = template::expand_env(template)?;
+    if template.contains(['\n', '\r']) {
+        return Err("a module banner must fit on one line".to_string());
+    }
+    Ok(template)
+}
+
+/// Reads a --package-metadata-from file, which must be a TOML table
+fn read_metadata_file(path: &Path) -> Result<toml::Table> {
+    cache::track(path);
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        ScriptifyError::ManifestError(format!(
+            "failed to read metadata file '{}': {e}",
+            path.display()
+        ))
+    })?;
+    toml::from_str(&content).map_err(|e| {
+        ScriptifyError::ManifestError(format!("invalid metadata file '{}': {e}", path.display()))
+    })
+}
+
+/// Merges a --package-metadata-from table and then `--metadata` entries into
+/// `[package.metadata]`. The manifest is re-serialized (dropping comments) only when there
+/// is metadata to add.
+fn add_metadata(
+    manifest_content: &str,
+    metadata_file: Option<toml::Table>,
+    metadata: &[(String, toml::Value)],
+) -> Result<String> {
+    if metadata_file.is_none() && metadata.is_empty() {
+        return Ok(manifest_content.to_string());
+    }
+
+    let mut manifest: toml::Table = toml::from_str(manifest_content)?;
+    let package_metadata = child_table(&mut manifest, "package", "package")
+        .and_then(|package| child_table(package, "metadata", "package.metadata"))?;
+
+    if let Some(file) = metadata_file {
+        merge_tables(package_metadata, file);
+    }
+
+    for (key, value) in metadata {
+        let (parents, leaf) = key
+            .rsplit_once('.')
+            .map_or((None, key.as_str()), |(p, l)| (Some(p), l));
+        let mut table = &mut *package_metadata;
+        let mut path = "package.metadata".to_string();
+        for segment in parents.into_iter().flat_map(|p| p.split('.')) {
+            path = format!("{path}.{segment}");
+            table = child_table(table, segment, &path)?;
+        }
+        table.insert(leaf.to_string(), value.clone());
+    }
+
+    toml::to_string(&manifest)
+        .map_err(|e| ScriptifyError::ManifestError(format!("failed to write manifest: {e}")))
+}
+
+/// Deep-merges `from` into `into`: tables present in both are merged key by key, any other
+/// value in `from` replaces the one in `into`
+fn merge_tables(into: &mut toml::Table, from: toml::Table) {
+    for (key, value) in from {
+        match (into.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => {
+                merge_tables(existing, table)
+            }
+            (_, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The table at `key`, created if missing
+fn child_table<'a>(
+    table: &'a mut toml::Table,
+    key: &str,
+    path: &str,
+) -> Result<&'a mut toml::Table> {
+    table
+        .entry(key)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| ScriptifyError::ManifestError(format!("`{path}` is not a table")))
 }
 
-fn get_shebang() -> String {
-    std::env::var("SCRIPTIFY_SHEBANG").unwrap_or_else(|_| DEFAULT_SHEBANG.to_string())
+/// Removes a `[dependencies]` header that is followed by no entries before the next table
+fn strip_empty_dependencies(manifest_content: &str) -> String {
+    let lines: Vec<&str> = manifest_content.lines().collect();
+    let mut stripped = String::with_capacity(manifest_content.len());
+
+    for (i, line) in lines.iter().enumerate() {
+        let is_empty_table = line.trim() == "[dependencies]"
+            && lines[i + 1..]
+                .iter()
+                .map(|l| l.trim())
+                .find(|l| !l.is_empty() && !l.starts_with('#'))
+                .is_none_or(|l| l.starts_with('['));
+
+        if !is_empty_table {
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+    }
+
+    stripped
 }
 
-fn build_cargo_script_empty(code: &str) -> String {
-    let shebang = get_shebang();
+/// Joins the shebang, the embedded manifest (in the target's format) and the code
+fn assemble_script(code: &str, manifest_content: &str, style: &ScriptStyle) -> String {
     let mut script = String::new();
 
-    script.push_str(&shebang);
+    if let Some(shebang) = &style.shebang {
+        check_shebang_length(shebang);
+        script.push_str(shebang);
+        script.push('\n');
+    }
+
+    match style.target {
+        ScriptTarget::CargoScript => {
+            script.push_str(FRONTMATTER_FENCE);
+            script.push_str(&style.section);
+            script.push('\n');
+            script.push_str(manifest_content);
+
+            if !manifest_content.is_empty() && !manifest_content.ends_with('\n') {
+                script.push('\n');
+            }
+
+            script.push_str(FRONTMATTER_CLOSE);
+        }
+        ScriptTarget::RustScript => {
+            script.push_str(DOC_MANIFEST_OPEN);
+            for line in manifest_content.lines() {
+                script.push_str("//!");
+                if !line.is_empty() {
+                    script.push(' ');
+                    script.push_str(line);
+                }
+                script.push('\n');
+            }
+            script.push_str(DOC_MANIFEST_CLOSE);
+        }
+    }
+
     script.push('\n');
-    script.push_str("---cargo\n");
-    script.push_str("[dependencies]\n");
-    script.push_str("---\n\n");
     script.push_str(code);
 
     script
 }
 
-fn build_cargo_script_with_manifest(manifest: &Path, code: &str) -> Result<String> {
-    let manifest_content = read_manifest(manifest)?;
-    let shebang = get_shebang();
-    let mut script = String::new();
+#[cfg(test)]
+mod tests {
+    use flate2::read::GzDecoder;
 
-    script.push_str(&shebang);
-    script.push('\n');
-    script.push_str("---cargo\n");
-    script.push_str(&manifest_content);
+    use super::*;
 
-    if !manifest_content.ends_with('\n') {
-        script.push('\n');
+    #[test]
+    fn gzip_round_trips() {
+        let script = "#!/usr/bin/env -S cargo +nightly -Zscript\n---cargo\n[dependencies]\n---\n\nfn main() {\n    println!(\"héllo\");\n}\n".repeat(20);
+        let mut decompressed = String::new();
+        GzDecoder::new(gzip(script.as_bytes()).unwrap().as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, script);
     }
 
-    script.push_str("---\n\n");
-    script.push_str(code);
+    #[test]
+    fn rust_script_target_writes_doc_manifest() {
+        let cli = Cli::parse_from([
+            "scriptify",
+            "src/lib.rs",
+            "--target",
+            "rust-script",
+            "--shebang-on",
+            "never",
+        ]);
+        let manifest = "[dependencies]\nregex = \"1\"\n\n[features]\ndefault = []\n";
+        assert_eq!(
+            assemble_script("fn main() {}\n", manifest, &script_style(&cli)),
+            "//! ```cargo\n\
+             //! [dependencies]\n\
+             //! regex = \"1\"\n\
+             //!\n\
+             //! [features]\n\
+             //! default = []\n\
+             //! ```\n\
+             \n\
+             fn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn cache_key_sees_expanded_placeholders() {
+        let cli = Cli::parse_from([
+            "scriptify",
+            "src/lib.rs",
+            "--metadata",
+            "built-by={{env.SCRIPTIFY_TEST_UNSET:-ci}}",
+        ]);
+        let options = format!("{cli:?}");
+        assert!(options.contains("\"ci\""), "{options}");
+        assert!(!options.contains("{{env."), "{options}");
+    }
+
+    #[test]
+    fn plain_neutralizes_decoration() {
+        let mut cli = Cli::parse_from([
+            "scriptify",
+            "src/lib.rs",
+            "--plain",
+            "--theme",
+            "nord",
+            "--auto-theme",
+            "nord",
+            "--theme-contrast-check",
+            "--color",
+            "always",
+            "--preview",
+            "nord",
+            "--timing",
+            "--group-by-module",
+            "--module-banner",
+            "// {module}",
+            "--truncate-strings",
+            "40",
+        ]);
+        apply_plain(&mut cli);
+        assert!(cli.theme.is_none() && cli.auto_theme.is_none());
+        assert!(!cli.theme_contrast_check);
+        assert!(cli.quiet);
+        assert!(cli.color == ColorChoice::Never);
+        assert!(cli.preview.is_none());
+        assert!(!cli.timing);
+        assert!(!cli.group_by_module && cli.module_banner.is_none());
+        assert!(cli.truncate_strings.is_none());
+
+        // --theme can't be combined with --output, which --diff needs
+        let mut cli = Cli::parse_from([
+            "scriptify",
+            "src/lib.rs",
+            "--plain",
+            "--output",
+            "script.rs",
+            "--diff",
+            "--diff-highlight",
+            "nord",
+        ]);
+        apply_plain(&mut cli);
+        assert!(cli.diff && cli.diff_highlight.is_none());
+    }
+
+    /// Loads a theme file with `content`, written to a scratch file named `name`
+    fn load_theme_file(name: &str, content: &str) -> Result<arborium::theme::Theme> {
+        let path = std::env::temp_dir().join(format!("scriptify-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let theme = load_theme(&path.display().to_string());
+        std::fs::remove_file(&path).unwrap();
+        theme
+    }
+
+    #[test]
+    fn theme_file_overlays_its_base() {
+        let keyword = arborium_theme::HIGHLIGHTS
+            .iter()
+            .position(|h| h.name == "keyword")
+            .unwrap();
+        let base = find_theme("dracula").unwrap();
+        let theme = load_theme_file(
+            "dracula-red.toml",
+            "name = \"Dracula Red\"\nbase = \"dracula\"\nkeyword = \"#ff0000\"\n",
+        )
+        .unwrap();
 
-    Ok(script)
+        assert_eq!(theme.name, "Dracula Red");
+        assert_eq!(
+            theme.styles[keyword].fg.map(|c| c.to_hex()).as_deref(),
+            Some("#ff0000")
+        );
+        assert_eq!(theme.background, base.background);
+        for (i, (style, builtin)) in theme.styles.iter().zip(&base.styles).enumerate() {
+            if i != keyword {
+                assert_eq!(style.fg, builtin.fg);
+            }
+        }
+    }
+
+    #[test]
+    fn theme_file_base_must_exist() {
+        let error = load_theme_file("missing-base.toml", "base = \"no such theme\"\n");
+        assert!(matches!(error, Err(ScriptifyError::ThemeNotFound { .. })));
+    }
+
+    #[test]
+    fn attributes_survive_inlining_and_formatting() {
+        let cli = Cli::parse_from(["scriptify", "src/main.rs"]);
+        let root =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/attributes/src/main.rs");
+        let source = std::fs::read_to_string(&root).unwrap();
+        let (file, errors) = inline::inline_source(&source, &root, &inline_options(&cli)).unwrap();
+        assert!(errors.is_empty());
+        let file = transform_inlined(&cli, file).unwrap();
+        let code = formatter(&cli).format(&file).unwrap();
+
+        let mut rest = code.as_str();
+        for attr in [
+            "#![allow(dead_code)]",
+            "#[derive(Debug, Clone, PartialEq)]\n    #[derive(serde::Serialize, serde::Deserialize)]",
+            "#[serde(rename_all = \"camelCase\")]",
+            "#[serde(rename = \"type\")]",
+            "#[cfg(feature = \"timestamps\")]",
+            "#[must_use]",
+            "#[cfg(feature = \"timestamps\")]",
+            "#[tokio::main(flavor = \"current_thread\")]\nasync fn main()",
+        ] {
+            let Some(at) = rest.find(attr) else {
+                panic!("`{attr}` is missing or out of order:\n{code}");
+            };
+            rest = &rest[at + attr.len()..];
+        }
+    }
+
+    #[test]
+    fn modules_emptied_by_exclusion_are_dropped() {
+        let cli = Cli::parse_from([
+            "scriptify",
+            "src/main.rs",
+            "--exclude-module",
+            "platform::wasm",
+            "--drop-empty-mods",
+        ]);
+        let source = "fn main() {}\n\
+                      mod platform {\n    mod wasm {\n        pub fn init() {}\n    }\n}\n\
+                      mod util {\n    pub fn id() {}\n}\n";
+        let (file, _) =
+            inline::inline_source(source, Path::new("src/main.rs"), &inline_options(&cli)).unwrap();
+        let file = transform_inlined(&cli, file).unwrap();
+        let modules: Vec<String> = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Mod(module) => Some(module.ident.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(modules, ["util"]);
+    }
+
+    /// Items in an order no sorting would produce, with rustfmt-sortable `use`s
+    const UNSORTED: &str = "use std::fmt;\n\
+                            use std::collections::HashMap;\n\
+                            #[derive(Debug, Clone)]\n\
+                            pub struct Zeta(HashMap<u8, u8>);\n\
+                            macro_rules! make { () => { fn made() {} }; }\n\
+                            make!();\n\
+                            fn main() { helper(); made(); }\n\
+                            mod b_empty {}\n\
+                            mod a_empty {}\n\
+                            pub enum Alpha { A }\n\
+                            fn helper() {}\n\
+                            fn unused() {}\n\
+                            impl fmt::Display for Zeta {\n\
+                                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(\"z\") }\n\
+                            }\n";
+
+    /// Asserts that the items of `UNSORTED` still in the output generated with `args`
+    /// come in their source order
+    fn assert_source_order(args: &[&str]) {
+        let cli = Cli::parse_from(["scriptify", "src/main.rs"].iter().chain(args));
+        let file = transform_inlined(&cli, syn::parse_file(UNSORTED).unwrap()).unwrap();
+        let code = formatter(&cli).format(&file).unwrap();
+
+        let positions: Vec<usize> = [
+            "use std::fmt;",
+            "use std::collections::HashMap;",
+            "pub struct Zeta",
+            "macro_rules! make",
+            "make!();",
+            "fn main()",
+            "mod b_empty",
+            "mod a_empty",
+            "pub enum Alpha",
+            "fn helper()",
+            "fn unused()",
+            "impl fmt::Display for Zeta",
+        ]
+        .iter()
+        .filter_map(|item| code.find(item))
+        .collect();
+        assert!(positions.is_sorted(), "{args:?} reordered items:\n{code}");
+    }
+
+    #[test]
+    fn transforms_keep_source_order() {
+        assert_source_order(&[]);
+        assert_source_order(&["--strip-private"]);
+        assert_source_order(&["--strip-derive", "Clone", "--drop-empty-mods"]);
+        assert_source_order(&["--resolve-reexports", "--trim-blank-lines"]);
+        assert_source_order(&["--preserve-item-order", "--strip-private"]);
+    }
+
+    #[test]
+    fn preserve_item_order_keeps_rustfmt_from_sorting() {
+        assert_source_order(&["--fmt-backend", "rustfmt", "--preserve-item-order"]);
+    }
+
+    #[test]
+    fn preserve_item_order_rejects_alphabetize_items() {
+        let error = Cli::try_parse_from([
+            "scriptify",
+            "src/main.rs",
+            "--preserve-item-order",
+            "--alphabetize-items",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn trim_blank_lines_collapses_runs() {
+        let code = "fn a() {}\n\n\n\nfn b() {}\n\nfn c() {}\n";
+        assert_eq!(
+            trim_blank_lines(code),
+            "fn a() {}\n\nfn b() {}\n\nfn c() {}\n"
+        );
+    }
+
+    #[test]
+    fn trim_blank_lines_keeps_string_literals() {
+        let code = "const S: &str = \"a\n\n\n\nb\";\n\n\n\nfn main() {}\n";
+        assert_eq!(
+            trim_blank_lines(code),
+            "const S: &str = \"a\n\n\n\nb\";\n\nfn main() {}\n"
+        );
+    }
+
+    fn bins_fixture() -> (toml::Value, PathBuf) {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bins");
+        (
+            toml::from_str("[package]\nname = \"bins\"\n").unwrap(),
+            base_dir,
+        )
+    }
+
+    #[test]
+    fn discover_bins_finds_convention_layouts() {
+        let (manifest, base_dir) = bins_fixture();
+        let bins = discover_bins(&manifest, &base_dir, false);
+        assert_eq!(bin_names(&bins), "server, tool");
+        assert_eq!(bins[0].path, base_dir.join("src/bin/server/main.rs"));
+        assert_eq!(bins[1].path, base_dir.join("src/bin/tool.rs"));
+    }
+
+    #[test]
+    fn select_bin_picks_by_name() {
+        let (manifest, base_dir) = bins_fixture();
+        assert_eq!(
+            select_bin(&manifest, &base_dir, "tool", false).unwrap(),
+            base_dir.join("src/bin/tool.rs")
+        );
+        let err = select_bin(&manifest, &base_dir, "client", false).unwrap_err();
+        assert!(err.to_string().contains("available: server, tool"), "{err}");
+    }
+
+    #[test]
+    fn several_bins_need_bin_flag() {
+        let (_, base_dir) = bins_fixture();
+        let err =
+            parse_entry_point("[package]\nname = \"bins\"\n", &base_dir, None, false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("multiple binaries found (server, tool)"),
+            "{err}"
+        );
+        assert_eq!(
+            parse_entry_point(
+                "[package]\nname = \"bins\"\n",
+                &base_dir,
+                Some("server"),
+                false
+            )
+            .unwrap(),
+            base_dir.join("src/bin/server/main.rs")
+        );
+    }
 }
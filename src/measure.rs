@@ -0,0 +1,92 @@
+//! `--measure-compile` and `--verify-run`: build (and run) a generated cargo-script in a
+//! scratch directory, to report the script's weight or prove that it works.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::{Result, ScriptifyError};
+
+/// Lines of a failing child's stderr included in the error
+const MAX_STDERR_LINES: usize = 20;
+
+/// Builds `script` with `cargo -Zscript build` and returns the elapsed time.
+/// The scratch directory (script and target dir) is removed afterwards.
+pub fn measure_compile(script: &str, nightly: bool) -> Result<Duration> {
+    in_scratch("measure", script, |script_path| {
+        let start = Instant::now();
+        let status = cargo_script(script_path, nightly, "build")
+            .stdout(Stdio::null())
+            .status()
+            .map_err(ScriptifyError::io("failed to run cargo"))?;
+        let elapsed = start.elapsed();
+
+        if !status.success() {
+            return Err(format!("building the generated script failed ({status})").into());
+        }
+
+        Ok(elapsed)
+    })
+}
+
+/// Builds `script`, then runs it without arguments or stdin, failing with the child's
+/// stderr if either step exits non-zero
+pub fn verify_run(script: &str, nightly: bool) -> Result<()> {
+    in_scratch("verify", script, |script_path| {
+        for (subcommand, step) in [("build", "building"), ("run", "running")] {
+            let output = cargo_script(script_path, nightly, subcommand)
+                .stdin(Stdio::null())
+                .output()
+                .map_err(ScriptifyError::io("failed to run cargo"))?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let lines: Vec<&str> = stderr.lines().collect();
+                let tail = &lines[lines.len().saturating_sub(MAX_STDERR_LINES)..];
+                return Err(format!(
+                    "{step} the generated script failed ({}):\n{}",
+                    output.status,
+                    tail.join("\n")
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Writes `script` into a fresh scratch directory, calls `f` with its path and removes
+/// the directory again
+fn in_scratch<T>(purpose: &str, script: &str, f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let scratch = std::env::temp_dir().join(format!("scriptify-{purpose}-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch).map_err(ScriptifyError::io(format!(
+        "failed to create '{}'",
+        scratch.display()
+    )))?;
+
+    let script_path = scratch.join("script.rs");
+    let result = std::fs::write(&script_path, script)
+        .map_err(ScriptifyError::io(format!(
+            "failed to write '{}'",
+            script_path.display()
+        )))
+        .and_then(|()| f(&script_path));
+    let _ = std::fs::remove_dir_all(&scratch);
+    result
+}
+
+/// `cargo -Zscript <subcommand> --quiet` for the script, with its target dir next to it
+fn cargo_script(script_path: &Path, nightly: bool, subcommand: &str) -> Command {
+    let mut command = Command::new("cargo");
+    if nightly {
+        command.arg("+nightly");
+    } else {
+        command.env("RUSTC_BOOTSTRAP", "1");
+    }
+    command
+        .args(["-Zscript", subcommand, "--quiet", "--manifest-path"])
+        .arg(script_path)
+        .env("CARGO_TARGET_DIR", script_path.with_file_name("target"));
+    command
+}
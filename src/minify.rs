@@ -0,0 +1,161 @@
+//! `--minify-manifest`: trims a crate's manifest down to what a single-file script uses.
+//! Build targets, dev- and build-dependencies and workspace settings are dropped, while
+//! everything that decides which code gets compiled stays intact: `[dependencies]`,
+//! `[target.'cfg(..)'.dependencies]` tables and the `[features]` that enable optional
+//! dependencies.
+
+use crate::{Result, ScriptifyError};
+
+/// Top-level tables a script has no use for
+const DROPPED_TABLES: &[&str] = &[
+    "lib",
+    "bin",
+    "example",
+    "test",
+    "bench",
+    "dev-dependencies",
+    "build-dependencies",
+    "workspace",
+    "badges",
+];
+
+/// `[package]` keys that still mean something for a script
+const KEPT_PACKAGE_KEYS: &[&str] = &["name", "version", "edition", "rust-version", "metadata"];
+
+/// Returns the minified manifest and the dotted names of what was removed
+pub fn minify_manifest(content: &str) -> Result<(String, Vec<String>)> {
+    let mut manifest: toml::Table = toml::from_str(content)?;
+    let mut removed = Vec::new();
+
+    for &table in DROPPED_TABLES {
+        if manifest.remove(table).is_some() {
+            removed.push(table.to_string());
+        }
+    }
+
+    if let Some(package) = manifest
+        .get_mut("package")
+        .and_then(toml::Value::as_table_mut)
+    {
+        package.retain(|key, _| {
+            let kept = KEPT_PACKAGE_KEYS.contains(&key);
+            if !kept {
+                removed.push(format!("package.{key}"));
+            }
+            kept
+        });
+    }
+
+    // Platform-specific dependencies stay, only their dev and build variants go
+    if let Some(targets) = manifest
+        .get_mut("target")
+        .and_then(toml::Value::as_table_mut)
+    {
+        for (cfg, target) in targets.iter_mut() {
+            let Some(target) = target.as_table_mut() else {
+                continue;
+            };
+            for kind in ["dev-dependencies", "build-dependencies"] {
+                if target.remove(kind).is_some() {
+                    removed.push(format!("target.{cfg}.{kind}"));
+                }
+            }
+        }
+        targets.retain(|_, target| target.as_table().is_none_or(|table| !table.is_empty()));
+        if targets.is_empty() {
+            manifest.remove("target");
+        }
+    }
+
+    let minified = toml::to_string(&manifest)
+        .map_err(|e| ScriptifyError::ManifestError(format!("failed to write manifest: {e}")))?;
+    Ok((minified, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = concat!(
+        "[package]\n",
+        "name = \"tool\"\n",
+        "version = \"0.1.0\"\n",
+        "edition = \"2024\"\n",
+        "description = \"A tool\"\n",
+        "build = \"build.rs\"\n",
+        "\n",
+        "[dependencies]\n",
+        "serde = { version = \"1\", optional = true }\n",
+        "\n",
+        "[target.'cfg(unix)'.dependencies]\n",
+        "libc = \"0.2\"\n",
+        "\n",
+        "[target.'cfg(unix)'.dev-dependencies]\n",
+        "tempfile = \"3\"\n",
+        "\n",
+        "[target.'cfg(windows)'.build-dependencies]\n",
+        "winres = \"0.1\"\n",
+        "\n",
+        "[features]\n",
+        "json = [\"dep:serde\"]\n",
+        "\n",
+        "[dev-dependencies]\n",
+        "insta = \"1\"\n",
+        "\n",
+        "[[bin]]\n",
+        "name = \"tool\"\n",
+        "path = \"src/main.rs\"\n",
+    );
+
+    fn minified() -> (toml::Table, Vec<String>) {
+        let (content, removed) = minify_manifest(MANIFEST).unwrap();
+        (toml::from_str(&content).unwrap(), removed)
+    }
+
+    #[test]
+    fn keeps_target_specific_dependencies() {
+        let (manifest, _) = minified();
+        assert_eq!(
+            manifest["target"]["cfg(unix)"]["dependencies"]["libc"].as_str(),
+            Some("0.2")
+        );
+    }
+
+    #[test]
+    fn drops_target_specific_dev_and_build_dependencies() {
+        let (manifest, removed) = minified();
+        let unix = manifest["target"]["cfg(unix)"].as_table().unwrap();
+        assert!(!unix.contains_key("dev-dependencies"));
+        assert!(
+            !manifest["target"]
+                .as_table()
+                .unwrap()
+                .contains_key("cfg(windows)")
+        );
+        assert!(removed.contains(&"target.cfg(unix).dev-dependencies".to_string()));
+    }
+
+    #[test]
+    fn keeps_optional_dependencies_and_their_features() {
+        let (manifest, _) = minified();
+        assert_eq!(
+            manifest["dependencies"]["serde"]["optional"].as_bool(),
+            Some(true)
+        );
+        assert_eq!(
+            manifest["features"]["json"].as_array().unwrap()[0].as_str(),
+            Some("dep:serde")
+        );
+    }
+
+    #[test]
+    fn drops_targets_and_package_fields_scripts_ignore() {
+        let (manifest, removed) = minified();
+        assert!(!manifest.contains_key("bin"));
+        assert!(!manifest.contains_key("dev-dependencies"));
+        let package = manifest["package"].as_table().unwrap();
+        assert!(!package.contains_key("build"));
+        assert!(package.contains_key("edition"));
+        assert!(removed.contains(&"package.description".to_string()));
+    }
+}
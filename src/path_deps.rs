@@ -0,0 +1,368 @@
+//! `--inline-path-deps`: inlines the library of each `path` dependency the code uses as a
+//! module, so a script spanning several local crates is self-contained. The dependency's
+//! `lib.rs` (with its own modules) becomes `mod <name> { ... }` at the crate root,
+//! `crate::` paths inside it gain the module name, and `<name>::` paths in the rest of the
+//! code become `crate::<name>::`. The dependency's own dependencies move into the
+//! manifest. `$crate` paths are rebased like `crate::` ones, and `#[macro_export]` macros,
+//! which live at the crate root, are reached as `crate::m!`. Only one level is inlined:
+//! path dependencies of an inlined crate stay dependencies. The rewrite is syntactic, so
+//! names brought into scope by glob imports aren't followed.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Group, Ident, Punct, Spacing, Span, TokenStream, TokenTree};
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{Item, ItemMod, ItemUse, UseTree};
+
+use crate::{Result, ScriptifyError};
+
+/// Crate-level attributes that are kept as inner attributes of the module
+const MODULE_ATTRIBUTES: &[&str] = &["doc", "allow", "warn", "deny", "forbid", "expect"];
+
+/// A `path` dependency of the manifest
+pub struct PathDep {
+    /// Manifest key, which is also the crate's name in code (with `-` as `_`)
+    pub key: String,
+    pub dir: PathBuf,
+}
+
+impl PathDep {
+    pub fn crate_name(&self) -> String {
+        self.key.replace('-', "_")
+    }
+}
+
+/// The `[dependencies]` entries of `manifest` with a `path`, made absolute from `manifest_dir`
+pub fn path_dependencies(manifest: &toml::Table, manifest_dir: &Path) -> Vec<PathDep> {
+    let Some(deps) = manifest.get("dependencies").and_then(toml::Value::as_table) else {
+        return Vec::new();
+    };
+    deps.iter()
+        .filter_map(|(key, dep)| {
+            let dir = manifest_dir.join(dep.get("path")?.as_str()?);
+            Some(PathDep {
+                key: key.clone(),
+                dir: std::path::absolute(&dir).unwrap_or(dir),
+            })
+        })
+        .collect()
+}
+
+/// The library entry point of the crate in `dir` (`[lib] path`, or `src/lib.rs`) and the
+/// crate's manifest
+pub fn library_entry(dir: &Path) -> Result<(PathBuf, toml::Table)> {
+    let manifest_path = dir.join("Cargo.toml");
+    let content = std::fs::read_to_string(&manifest_path).map_err(|e| {
+        ScriptifyError::ManifestError(format!(
+            "failed to read manifest '{}': {e}",
+            manifest_path.display()
+        ))
+    })?;
+    let manifest: toml::Table = toml::from_str(&content)?;
+
+    let entry = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("path"))
+        .and_then(toml::Value::as_str)
+        .map_or_else(|| dir.join("src/lib.rs"), |path| dir.join(path));
+    if !entry.is_file() {
+        return Err(ScriptifyError::ManifestError(format!(
+            "'{}' has no library target (looked for '{}')",
+            dir.display(),
+            entry.display()
+        )));
+    }
+    Ok((entry, manifest))
+}
+
+/// Appends `library` to `file` as `pub mod <name>` and points the paths of both at it.
+/// Returns a warning for each crate-level attribute of the library that is dropped.
+pub fn nest_crate(file: &mut syn::File, name: &str, mut library: syn::File) -> Vec<String> {
+    let mut exported = ExportedMacros::default();
+    exported.visit_file(&library);
+    let rewrite = Rewriter {
+        name,
+        exported: &exported.0,
+    };
+    Rebase(&rewrite).visit_file_mut(&mut library);
+    Redirect(&rewrite).visit_file_mut(file);
+
+    let mut warnings = Vec::new();
+    let mut attrs = Vec::new();
+    for attr in library.attrs {
+        if MODULE_ATTRIBUTES
+            .iter()
+            .any(|kept| attr.path().is_ident(kept))
+        {
+            attrs.push(attr);
+        } else {
+            warnings.push(format!(
+                "dropped crate-level attribute `#![{}]` of `{name}`",
+                path_string(attr.path())
+            ));
+        }
+    }
+
+    // `extern crate <name>;` and root-level imports of exported macros would clash with
+    // the module and the macros themselves
+    file.items.retain(|item| match item {
+        Item::ExternCrate(extern_crate) => {
+            extern_crate.ident != name || extern_crate.rename.is_some()
+        }
+        Item::Use(item) => !matches!(&item.tree, UseTree::Path(root)
+            if root.ident == "crate"
+                && matches!(&*root.tree, UseTree::Name(leaf) if exported.0.contains(&leaf.ident.to_string()))),
+        _ => true,
+    });
+    file.items.push(Item::Mod(ItemMod {
+        attrs,
+        vis: syn::parse_quote!(pub),
+        unsafety: None,
+        mod_token: Default::default(),
+        ident: Ident::new(name, Span::call_site()),
+        content: Some((Default::default(), library.items)),
+        semi: None,
+    }));
+    warnings
+}
+
+fn path_string(path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Names of the `#[macro_export]` macros, which live at the crate root wherever they are
+/// defined and so stay reachable as `crate::m!` after nesting
+#[derive(Default)]
+struct ExportedMacros(HashSet<String>);
+
+impl<'ast> Visit<'ast> for ExportedMacros {
+    fn visit_item_macro(&mut self, item: &'ast syn::ItemMacro) {
+        if let Some(ident) = &item.ident
+            && item
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("macro_export"))
+        {
+            self.0.insert(ident.to_string());
+        }
+    }
+}
+
+/// The path rewrites for nesting the library `name`
+struct Rewriter<'a> {
+    name: &'a str,
+    exported: &'a HashSet<String>,
+}
+
+impl Rewriter<'_> {
+    fn ident(&self, name: &str) -> Ident {
+        Ident::new(name, Span::call_site())
+    }
+
+    fn is_exported_macro(&self, segment: Option<&syn::PathSegment>, is_macro: bool) -> bool {
+        is_macro
+            && segment.is_some_and(|segment| self.exported.contains(&segment.ident.to_string()))
+    }
+
+    /// `crate::x` becomes `crate::<name>::x` inside the library
+    fn rebase_path(&self, path: &mut syn::Path, is_macro: bool) {
+        if path.leading_colon.is_none()
+            && path.segments.len() > 1
+            && path.segments[0].ident == "crate"
+            && !self.is_exported_macro(path.segments.get(1), is_macro)
+        {
+            path.segments.insert(1, self.ident(self.name).into());
+        }
+    }
+
+    /// `<name>::x` becomes `crate::<name>::x` outside the library, and `<name>::m!` of an
+    /// exported macro becomes `crate::m!`
+    fn redirect_path(&self, path: &mut syn::Path, is_macro: bool) {
+        if path.segments.len() < 2 || path.segments[0].ident != self.name {
+            return;
+        }
+        path.leading_colon = None;
+        if self.is_exported_macro(path.segments.get(1), is_macro) {
+            path.segments[0].ident = self.ident("crate");
+        } else {
+            path.segments.insert(0, self.ident("crate").into());
+        }
+    }
+
+    /// Puts `prefix::` in front of a `use` tree
+    fn prefix_use(&self, tree: &mut UseTree, prefix: &str) {
+        let rest = std::mem::replace(
+            tree,
+            UseTree::Glob(syn::UseGlob {
+                star_token: Default::default(),
+            }),
+        );
+        *tree = UseTree::Path(syn::UsePath {
+            ident: self.ident(prefix),
+            colon2_token: Default::default(),
+            tree: Box::new(rest),
+        });
+    }
+
+    /// `crate ::` (or `$crate ::`) becomes `crate :: <name> ::` in the library's macro
+    /// tokens, except before an exported macro
+    fn rebase_tokens(&self, tokens: TokenStream) -> TokenStream {
+        rewrite_tokens(tokens, &|tokens, i| {
+            let is_crate = matches!(&tokens[i], TokenTree::Ident(ident) if ident == "crate");
+            let ahead = &tokens[i + 1..];
+            (is_crate && is_path_separator(ahead) && !self.names_exported_macro(&ahead[2..])).then(
+                || {
+                    let mut replacement = vec![tokens[i].clone()];
+                    replacement.extend(path_separator());
+                    replacement.push(TokenTree::Ident(self.ident(self.name)));
+                    replacement
+                },
+            )
+        })
+    }
+
+    /// `<name> ::` becomes `crate :: <name> ::` in macro tokens outside the library, unless
+    /// it follows a `::`, `.` or `$` (a path segment, field or metavariable rather than a
+    /// path root); `<name> :: m !` of an exported macro becomes `crate :: m !`
+    fn redirect_tokens(&self, tokens: TokenStream) -> TokenStream {
+        rewrite_tokens(tokens, &|tokens, i| {
+            let is_name = matches!(&tokens[i], TokenTree::Ident(ident) if ident == self.name);
+            let after_path = i > 0
+                && matches!(&tokens[i - 1], TokenTree::Punct(punct) if matches!(punct.as_char(), ':' | '.' | '$'));
+            let ahead = &tokens[i + 1..];
+            if !is_name || after_path || !is_path_separator(ahead) {
+                return None;
+            }
+            let mut replacement = vec![TokenTree::Ident(self.ident("crate"))];
+            if !self.names_exported_macro(&ahead[2..]) {
+                replacement.extend(path_separator());
+                replacement.push(tokens[i].clone());
+            }
+            Some(replacement)
+        })
+    }
+
+    /// Whether `tokens` start with `m !` for an exported macro `m`
+    fn names_exported_macro(&self, tokens: &[TokenTree]) -> bool {
+        matches!(
+            tokens,
+            [TokenTree::Ident(ident), TokenTree::Punct(bang), ..]
+                if bang.as_char() == '!' && self.exported.contains(&ident.to_string())
+        )
+    }
+}
+
+/// Points the nested library's own `crate::` paths at its module
+struct Rebase<'a>(&'a Rewriter<'a>);
+
+impl VisitMut for Rebase<'_> {
+    fn visit_path_mut(&mut self, path: &mut syn::Path) {
+        self.0.rebase_path(path, false);
+        syn::visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_item_use_mut(&mut self, item: &mut ItemUse) {
+        if let UseTree::Path(root) = &mut item.tree
+            && root.ident == "crate"
+            && !matches!(&*root.tree, UseTree::Name(leaf) if self.0.exported.contains(&leaf.ident.to_string()))
+        {
+            self.0.prefix_use(&mut root.tree, self.0.name);
+        }
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        self.0.rebase_path(&mut mac.path, true);
+        mac.tokens = self.0.rebase_tokens(mac.tokens.clone());
+    }
+}
+
+/// Points the rest of the code's `<name>::` paths at the nested library
+struct Redirect<'a>(&'a Rewriter<'a>);
+
+impl VisitMut for Redirect<'_> {
+    fn visit_path_mut(&mut self, path: &mut syn::Path) {
+        self.0.redirect_path(path, false);
+        syn::visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_item_use_mut(&mut self, item: &mut ItemUse) {
+        let UseTree::Path(root) = &mut item.tree else {
+            return;
+        };
+        if root.ident != self.0.name {
+            return;
+        }
+        item.leading_colon = None;
+        match &*root.tree {
+            UseTree::Name(leaf) if self.0.exported.contains(&leaf.ident.to_string()) => {
+                root.ident = self.0.ident("crate");
+            }
+            _ => self.0.prefix_use(&mut item.tree, "crate"),
+        }
+    }
+
+    /// `extern crate <name> as alias;` becomes `use crate::<name> as alias;` (the unrenamed
+    /// form is removed by `nest_crate`)
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        if let Item::ExternCrate(extern_crate) = item
+            && extern_crate.ident == self.0.name
+            && let Some((_, rename)) = &extern_crate.rename
+        {
+            let vis = &extern_crate.vis;
+            let name = self.0.ident(self.0.name);
+            *item = syn::parse_quote!(#vis use crate::#name as #rename;);
+            return;
+        }
+        syn::visit_mut::visit_item_mut(self, item);
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        self.0.redirect_path(&mut mac.path, true);
+        mac.tokens = self.0.redirect_tokens(mac.tokens.clone());
+    }
+}
+
+type Rewrite<'a> = dyn Fn(&[TokenTree], usize) -> Option<Vec<TokenTree>> + 'a;
+
+/// Replaces tokens for which `rewrite` returns a replacement, recursing into groups
+fn rewrite_tokens(tokens: TokenStream, rewrite: &Rewrite<'_>) -> TokenStream {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut rewritten = Vec::with_capacity(tokens.len());
+    for (i, token) in tokens.iter().enumerate() {
+        if let Some(replacement) = rewrite(&tokens, i) {
+            rewritten.extend(replacement);
+            continue;
+        }
+        match token {
+            TokenTree::Group(group) => {
+                let mut nested =
+                    Group::new(group.delimiter(), rewrite_tokens(group.stream(), rewrite));
+                nested.set_span(group.span());
+                rewritten.push(TokenTree::Group(nested));
+            }
+            _ => rewritten.push(token.clone()),
+        }
+    }
+    rewritten.into_iter().collect()
+}
+
+fn is_path_separator(tokens: &[TokenTree]) -> bool {
+    matches!(
+        tokens,
+        [TokenTree::Punct(first), TokenTree::Punct(second), ..]
+            if first.as_char() == ':' && first.spacing() == Spacing::Joint && second.as_char() == ':'
+    )
+}
+
+fn path_separator() -> [TokenTree; 2] {
+    [
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+    ]
+}
@@ -0,0 +1,131 @@
+use arborium::theme::builtin;
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::{Result, ScriptifyError, apply_syntax_highlighting};
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const RESET: &str = "\x1b[0m";
+
+enum Key {
+    Next,
+    Previous,
+    Select,
+    Quit,
+    Other,
+}
+
+/// Cycles through the builtin themes, rendering `output` with each one.
+/// Returns the selected theme name, or `None` if the picker was dismissed.
+pub fn pick_theme(output: &str, language: &str) -> Result<Option<String>> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Err(ScriptifyError::Usage(
+            "--pick-theme requires an interactive terminal".to_string(),
+        ));
+    }
+
+    let themes: Vec<String> = builtin::all().into_iter().map(|t| t.name).collect();
+    let rows = terminal_rows().unwrap_or(24);
+
+    // Raw-ish mode through stty keeps us free of a terminal dependency
+    let saved = stty(&["-g"])?;
+    stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+
+    let result = select(&themes, output, rows, language);
+
+    stty(&[saved.trim()])?;
+    print!("{CLEAR_SCREEN}");
+    std::io::stdout().flush()?;
+
+    result
+}
+
+fn select(themes: &[String], output: &str, rows: usize, language: &str) -> Result<Option<String>> {
+    let mut index = 0;
+
+    loop {
+        render(themes, index, output, rows, language)?;
+
+        match read_key()? {
+            Key::Next => index = (index + 1) % themes.len(),
+            Key::Previous => index = (index + themes.len() - 1) % themes.len(),
+            Key::Select => return Ok(Some(themes[index].clone())),
+            Key::Quit => return Ok(None),
+            Key::Other => {}
+        }
+    }
+}
+
+fn render(
+    themes: &[String],
+    index: usize,
+    output: &str,
+    rows: usize,
+    language: &str,
+) -> Result<()> {
+    let name = &themes[index];
+    let highlighted = apply_syntax_highlighting(output, Some(name), language)?;
+    let mut stdout = std::io::stdout().lock();
+
+    write!(stdout, "{CLEAR_SCREEN}")?;
+    for line in highlighted.lines().take(rows.saturating_sub(1)) {
+        writeln!(stdout, "{line}")?;
+    }
+    write!(
+        stdout,
+        "{RESET}[{}/{}] {name}  (←/→ cycle, Enter select, q quit)",
+        index + 1,
+        themes.len()
+    )?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+fn read_key() -> Result<Key> {
+    let mut stdin = std::io::stdin().lock();
+    let mut byte = [0u8; 1];
+    stdin.read_exact(&mut byte)?;
+
+    let key = match byte[0] {
+        b'\r' | b'\n' => Key::Select,
+        b'q' | 3 => Key::Quit,
+        b'l' | b'j' | b' ' => Key::Next,
+        b'h' | b'k' => Key::Previous,
+        0x1b => {
+            let mut sequence = [0u8; 2];
+            stdin.read_exact(&mut sequence)?;
+            match sequence {
+                [b'[', b'C' | b'B'] => Key::Next,
+                [b'[', b'D' | b'A'] => Key::Previous,
+                _ => Key::Other,
+            }
+        }
+        _ => Key::Other,
+    };
+
+    Ok(key)
+}
+
+fn terminal_rows() -> Option<usize> {
+    let size = stty(&["size"]).ok()?;
+    size.split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+        .filter(|&rows| rows > 0)
+}
+
+fn stty(args: &[&str]) -> Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("failed to run stty: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!("stty {} failed", args.join(" ")).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
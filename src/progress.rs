@@ -0,0 +1,66 @@
+//! A module counter on stderr while inlining large crates, so long runs don't look
+//! hung. It only appears on a terminal and after a short delay, so quick runs stay
+//! silent.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+const DELAY: Duration = Duration::from_millis(300);
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+pub struct Progress {
+    enabled: bool,
+    start: Instant,
+    count: Cell<usize>,
+    shown: Cell<bool>,
+    last_draw: Cell<Option<Instant>>,
+}
+
+impl Progress {
+    /// Starts counting; nothing is ever printed unless `enabled`
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            start: Instant::now(),
+            count: Cell::new(0),
+            shown: Cell::new(false),
+            last_draw: Cell::new(None),
+        }
+    }
+
+    /// Records one more module file read
+    pub fn tick(&self) {
+        let count = self.count.get() + 1;
+        self.count.set(count);
+
+        if !self.enabled || self.start.elapsed() < DELAY {
+            return;
+        }
+        let now = Instant::now();
+        if self.shown.get()
+            && self
+                .last_draw
+                .get()
+                .is_some_and(|last| now - last < REDRAW_INTERVAL)
+        {
+            return;
+        }
+
+        let frame = (now - self.start).as_millis() / REDRAW_INTERVAL.as_millis();
+        eprint!(
+            "{CLEAR_LINE}{} inlining: {count} modules",
+            SPINNER[frame as usize % SPINNER.len()]
+        );
+        self.shown.set(true);
+        self.last_draw.set(Some(now));
+    }
+
+    /// Erases the counter, e.g. before printing a warning; the next tick redraws it
+    pub fn clear(&self) {
+        if self.shown.replace(false) {
+            eprint!("{CLEAR_LINE}");
+        }
+    }
+}
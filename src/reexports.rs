@@ -0,0 +1,222 @@
+//! `--resolve-reexports`: finds `pub use` re-exports that point at another re-export
+//! and annotates them with the item's origin. Conservative by design: paths are only
+//! followed through modules of the inlined crate, glob re-exports are ignored, and no
+//! code is rewritten.
+
+use std::collections::{HashMap, HashSet};
+
+use syn::visit_mut::VisitMut;
+use syn::{Item, ItemUse, UseTree, Visibility};
+
+use crate::comments;
+
+/// A followed re-export chain, from the re-export itself to the item's origin
+pub struct Chain {
+    hops: Vec<Vec<String>>,
+}
+
+impl Chain {
+    fn origin(&self) -> String {
+        display_path(self.hops.last().expect("chains have at least two hops"))
+    }
+}
+
+impl std::fmt::Display for Chain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hops: Vec<String> = self.hops.iter().map(|hop| display_path(hop)).collect();
+        write!(f, "{}", hops.join(" -> "))
+    }
+}
+
+/// Annotates every re-export of `file` that goes through another re-export with a
+/// comment naming its origin, returning the chains found
+pub fn annotate_chains(file: &mut syn::File) -> Vec<Chain> {
+    let mut collector = Collector::default();
+    collector.collect(&file.items, &[]);
+
+    let mut annotator = Annotator {
+        collector: &collector,
+        module_path: Vec::new(),
+        chains: Vec::new(),
+    };
+    annotator.visit_file_mut(file);
+    annotator.chains
+}
+
+/// A `pub use` binding: the name it introduces and the path it refers to
+struct Reexport {
+    name: String,
+    path: Vec<String>,
+    leading_colon: bool,
+}
+
+#[derive(Default)]
+struct Collector {
+    /// Modules of the crate, by absolute path (the root is empty)
+    modules: HashSet<Vec<String>>,
+    /// Resolved target of each re-export, keyed by the path it makes available
+    targets: HashMap<Vec<String>, Vec<String>>,
+}
+
+impl Collector {
+    fn collect(&mut self, items: &[Item], module: &[String]) {
+        self.modules.insert(module.to_vec());
+        for item in items {
+            if let Item::Mod(item) = item
+                && let Some((_, content)) = &item.content
+            {
+                let mut child = module.to_vec();
+                child.push(item.ident.to_string());
+                self.collect(content, &child);
+            }
+        }
+
+        for item in items {
+            let Item::Use(item) = item else { continue };
+            for reexport in public_reexports(item) {
+                if let Some(target) = self.resolve(module, &reexport) {
+                    let mut key = module.to_vec();
+                    key.push(reexport.name);
+                    self.targets.insert(key, target);
+                }
+            }
+        }
+    }
+
+    /// Absolute path of a re-export's target, if it lies within the crate
+    fn resolve(&self, module: &[String], reexport: &Reexport) -> Option<Vec<String>> {
+        if reexport.leading_colon {
+            return None;
+        }
+
+        let (first, rest) = reexport.path.split_first()?;
+        let mut resolved = match first.as_str() {
+            "crate" => Vec::new(),
+            "self" => module.to_vec(),
+            "super" => module.split_last()?.1.to_vec(),
+            _ => {
+                let mut child = module.to_vec();
+                child.push(first.clone());
+                // Anything but a child module is an external crate or a local item
+                if !self.modules.contains(&child) {
+                    return None;
+                }
+                child
+            }
+        };
+
+        for segment in rest {
+            match segment.as_str() {
+                "super" => {
+                    resolved.pop()?;
+                }
+                "self" => {}
+                _ => resolved.push(segment.clone()),
+            }
+        }
+
+        Some(resolved)
+    }
+
+    /// The hops from `start` through further re-exports, ending at the origin
+    fn follow(&self, start: &[String]) -> Vec<Vec<String>> {
+        let mut hops = vec![start.to_vec()];
+        while let Some(target) = self.targets.get(hops.last().expect("hops is never empty")) {
+            if hops.contains(target) {
+                break;
+            }
+            hops.push(target.clone());
+        }
+        hops
+    }
+}
+
+fn public_reexports(item: &ItemUse) -> Vec<Reexport> {
+    if matches!(item.vis, Visibility::Inherited) {
+        return Vec::new();
+    }
+
+    let mut reexports = Vec::new();
+    flatten(&item.tree, &mut Vec::new(), &mut reexports);
+    for reexport in &mut reexports {
+        reexport.leading_colon = item.leading_colon.is_some();
+    }
+    reexports
+}
+
+fn flatten(tree: &UseTree, prefix: &mut Vec<String>, reexports: &mut Vec<Reexport>) {
+    let mut push = |name: String, last: String| {
+        let mut path = prefix.clone();
+        if last != "self" {
+            path.push(last);
+        }
+        reexports.push(Reexport {
+            name,
+            path,
+            leading_colon: false,
+        });
+    };
+
+    match tree {
+        UseTree::Path(path) => {
+            prefix.push(path.ident.to_string());
+            flatten(&path.tree, prefix, reexports);
+            prefix.pop();
+        }
+        UseTree::Name(name) if name.ident == "self" => {
+            if let Some(module) = prefix.last().cloned() {
+                push(module, "self".to_string());
+            }
+        }
+        UseTree::Name(name) => push(name.ident.to_string(), name.ident.to_string()),
+        UseTree::Rename(rename) => push(rename.rename.to_string(), rename.ident.to_string()),
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                flatten(tree, prefix, reexports);
+            }
+        }
+        // Glob re-exports can't be followed without full name resolution
+        UseTree::Glob(_) => {}
+    }
+}
+
+fn display_path(path: &[String]) -> String {
+    std::iter::once("crate")
+        .chain(path.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+struct Annotator<'a> {
+    collector: &'a Collector,
+    module_path: Vec<String>,
+    chains: Vec<Chain>,
+}
+
+impl VisitMut for Annotator<'_> {
+    fn visit_item_mod_mut(&mut self, item: &mut syn::ItemMod) {
+        self.module_path.push(item.ident.to_string());
+        syn::visit_mut::visit_item_mod_mut(self, item);
+        self.module_path.pop();
+    }
+
+    fn visit_item_use_mut(&mut self, item: &mut ItemUse) {
+        for reexport in public_reexports(item) {
+            let mut start = self.module_path.clone();
+            start.push(reexport.name.clone());
+            let hops = self.collector.follow(&start);
+
+            // A direct re-export already names its origin
+            if hops.len() > 2 {
+                let chain = Chain { hops };
+                let comment = format!(
+                    " `{}` re-exports `{}` through other re-exports",
+                    reexport.name,
+                    chain.origin()
+                );
+                item.attrs.push(comments::marker(&comment));
+                self.chains.push(chain);
+            }
+        }
+    }
+}
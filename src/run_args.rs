@@ -0,0 +1,44 @@
+//! `--append-run-args`: bakes default command-line arguments into a script by
+//! wrapping `main` so that, when run without arguments, the script re-runs itself
+//! with the defaults. Re-executing keeps `std::env::args` working for any parser.
+
+use syn::{Ident, Item, ItemFn, parse_quote};
+
+const RENAMED_MAIN: &str = "__scriptify_main";
+
+/// Wraps the crate's `fn main`, returning `false` if there is none
+pub fn inject_default_args(file: &mut syn::File, args: &[String]) -> bool {
+    let Some(main) = file.items.iter_mut().find_map(|item| match item {
+        Item::Fn(function) if function.sig.ident == "main" => Some(function),
+        _ => None,
+    }) else {
+        return false;
+    };
+
+    let renamed = Ident::new(RENAMED_MAIN, main.sig.ident.span());
+    main.sig.ident = renamed.clone();
+    let output = main.sig.output.clone();
+
+    let invocation = format!(
+        " Runs with default arguments when none are given: `{}`",
+        args.join(" ")
+    );
+    let wrapper: ItemFn = parse_quote! {
+        #[doc = #invocation]
+        fn main() #output {
+            if std::env::args_os().len() <= 1 {
+                let exe = std::env::current_exe().expect("failed to locate the running script");
+                let status = std::process::Command::new(exe)
+                    .args([#(#args),*])
+                    .status()
+                    .expect("failed to re-run with default arguments");
+                std::process::exit(status.code().unwrap_or(1));
+            }
+
+            #renamed()
+        }
+    };
+
+    file.items.push(Item::Fn(wrapper));
+    true
+}
@@ -0,0 +1,169 @@
+//! `scriptify self-test`: runs the pipeline on a fixture built into the binary, to tell
+//! "scriptify is broken" apart from "my input is broken".
+
+use std::path::{Path, PathBuf};
+
+use arborium::theme::builtin;
+use clap::Parser;
+
+use crate::{Cli, DEFAULT_SHEBANG, FRONTMATTER_CLOSE, Result, ScriptifyError};
+
+/// Crate root of the fixture, declaring a module that lives in its own file
+const FIXTURE_MAIN: &str = "mod greet;
+
+fn main() {
+    greet::hello(\"world\");
+}
+";
+
+/// `greet.rs` next to the fixture's crate root
+const FIXTURE_GREET: &str = "//! Greetings
+
+/// Prints a greeting
+pub fn hello(name: &str) {
+    println!(\"Hello, {name}!\");
+}
+";
+
+/// What `scriptify -e --shebang-on always` makes of the fixture
+const EXPECTED: &str = "\
+#!/usr/bin/env -S RUSTC_BOOTSTRAP=1 RUSTFLAGS=-Coverflow-checks cargo run -qZscript --release --manifest-path
+---cargo
+[dependencies]
+---
+
+mod greet {
+    //! Greetings
+    /// Prints a greeting
+    pub fn hello(name: &str) {
+        println!(\"Hello, {name}!\");
+    }
+}
+fn main() {
+    greet::hello(\"world\");
+}
+";
+
+/// Runs every check, prints a line per check and returns whether all of them passed
+pub fn self_test() -> bool {
+    let generated = generate_fixture();
+    let checks: [(&str, std::result::Result<(), String>); 3] = [
+        (
+            "inline the built-in two-module fixture",
+            generated
+                .as_ref()
+                .map_err(Clone::clone)
+                .and_then(|output| compare(output, EXPECTED)),
+        ),
+        (
+            "generate cargo-script frontmatter",
+            generated
+                .as_ref()
+                .map_err(Clone::clone)
+                .and_then(|output| check_frontmatter(output)),
+        ),
+        ("load a theme and highlight", check_highlighting()),
+    ];
+
+    let mut failed = 0;
+    for (name, outcome) in &checks {
+        match outcome {
+            Ok(()) => println!("  ok    {name}"),
+            Err(reason) => {
+                failed += 1;
+                println!("  FAIL  {name}: {reason}");
+            }
+        }
+    }
+
+    if failed == 0 {
+        println!("self-test passed ({} checks)", checks.len());
+    } else {
+        println!("self-test failed ({failed} of {} checks)", checks.len());
+    }
+    failed == 0
+}
+
+/// Writes the fixture to a scratch directory and runs it through `generate` as a fresh
+/// command line would, so the user's flags and environment don't change the result
+fn generate_fixture() -> std::result::Result<String, String> {
+    let scratch = std::env::temp_dir().join(format!("scriptify-self-test-{}", std::process::id()));
+    let result = write_fixture(&scratch).and_then(|main| {
+        let cli = Cli::try_parse_from([
+            "scriptify".as_ref(),
+            main.as_os_str(),
+            "--empty-manifest".as_ref(),
+            "--shebang-on=always".as_ref(),
+            format!("--shebang={DEFAULT_SHEBANG}").as_ref(),
+        ])
+        .map_err(|e| ScriptifyError::Other(e.to_string()))?;
+        crate::generate(&cli, &main, None)
+    });
+    let _ = std::fs::remove_dir_all(&scratch);
+    result.map_err(|e| e.to_string())
+}
+
+/// Writes the fixture's files into `dir`, returning the path of its crate root
+fn write_fixture(dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).map_err(ScriptifyError::io(format!(
+        "failed to create '{}'",
+        dir.display()
+    )))?;
+    for (name, content) in [("main.rs", FIXTURE_MAIN), ("greet.rs", FIXTURE_GREET)] {
+        let path = dir.join(name);
+        std::fs::write(&path, content).map_err(ScriptifyError::io(format!(
+            "failed to write '{}'",
+            path.display()
+        )))?;
+    }
+    Ok(dir.join("main.rs"))
+}
+
+/// Points at the first line that differs from the golden output
+fn compare(output: &str, expected: &str) -> std::result::Result<(), String> {
+    if output == expected {
+        return Ok(());
+    }
+
+    let mut actual_lines = output.lines();
+    for (number, expected_line) in expected.lines().enumerate() {
+        let actual_line = actual_lines.next();
+        if actual_line != Some(expected_line) {
+            return Err(format!(
+                "output differs at line {}: expected `{expected_line}`, got `{}`",
+                number + 1,
+                actual_line.unwrap_or("<end of output>")
+            ));
+        }
+    }
+    Err(format!(
+        "output has unexpected trailing content after line {}",
+        expected.lines().count()
+    ))
+}
+
+/// The frontmatter must be found again and hold a manifest that parses
+fn check_frontmatter(output: &str) -> std::result::Result<(), String> {
+    let (_, manifest, code) =
+        crate::split_frontmatter(output).ok_or("no `---cargo` frontmatter in the output")?;
+    toml::from_str::<toml::Table>(manifest)
+        .map_err(|e| format!("the embedded manifest is not valid TOML: {e}"))?;
+    if !code.starts_with(FRONTMATTER_CLOSE) {
+        return Err("the frontmatter is not closed".to_string());
+    }
+    Ok(())
+}
+
+/// The first built-in theme must be found by name and color the fixture
+fn check_highlighting() -> std::result::Result<(), String> {
+    let theme = builtin::all()
+        .into_iter()
+        .next()
+        .ok_or("this build has no themes")?;
+    let highlighted =
+        crate::highlight_output(EXPECTED, &theme.name, "rust").map_err(|e| e.to_string())?;
+    if !highlighted.contains("\x1b[") {
+        return Err(format!("theme `{}` produced no colors", theme.name));
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! `--explain`: breaks a shebang line into its parts and describes what each one does,
+//! so the default can be customized without guessing.
+
+/// One word of the shebang and what it does
+pub struct Part {
+    pub word: String,
+    pub meaning: String,
+}
+
+/// Describes every word of `shebang` (with or without the leading `#!`), followed by the
+/// script path the kernel appends when the script is executed
+pub fn explain(shebang: &str) -> Vec<Part> {
+    let line = shebang.strip_prefix("#!").unwrap_or(shebang).trim();
+    let mut words = line.split_whitespace().peekable();
+    let mut parts = Vec::new();
+    let mut push = |word: &str, meaning: String| {
+        parts.push(Part {
+            word: word.to_string(),
+            meaning,
+        });
+    };
+
+    let Some(interpreter) = words.next() else {
+        return parts;
+    };
+    let mut program = interpreter;
+    if interpreter.rsplit('/').next() == Some("env") {
+        push(
+            interpreter,
+            "runs the command after it, looked up on PATH".to_string(),
+        );
+        if words.peek() == Some(&"-S") {
+            push(
+                "-S",
+                "splits the rest of the line into separate arguments, since the kernel passes it to env as one (GNU coreutils 8.30+, BSD/macOS env)".to_string(),
+            );
+            words.next();
+        }
+        while let Some(assignment) = words.next_if(|word| word.contains('=')) {
+            let (name, value) = assignment.split_once('=').expect("checked above");
+            push(assignment, env_meaning(name, value));
+        }
+        match words.next() {
+            Some(command) => {
+                push(command, program_meaning(command));
+                program = command;
+            }
+            None => return parts,
+        }
+    } else {
+        push(interpreter, program_meaning(interpreter));
+    }
+
+    for word in words {
+        match word.strip_prefix('-') {
+            // Combined short flags like `-qZscript`: `-Z` takes the rest as its value
+            Some(flags) if !flags.starts_with('-') && flags.len() > 1 && program == "cargo" => {
+                let split = flags.find('Z').unwrap_or(flags.len());
+                for flag in flags[..split].chars() {
+                    let flag = format!("-{flag}");
+                    push(&flag, flag_meaning(&flag));
+                }
+                if split < flags.len() {
+                    let flag = format!("-{}", &flags[split..]);
+                    push(&flag, flag_meaning(&flag));
+                }
+            }
+            _ if program == "cargo" => push(word, flag_meaning(word)),
+            _ => push(word, format!("passed to `{program}` as is")),
+        }
+    }
+
+    push(
+        "<script path>",
+        "appended by the kernel when the script is executed".to_string(),
+    );
+    parts
+}
+
+fn env_meaning(name: &str, value: &str) -> String {
+    match name {
+        "RUSTC_BOOTSTRAP" => {
+            "lets a stable toolchain accept unstable flags such as `-Zscript`".to_string()
+        }
+        "RUSTFLAGS" if value == "-Coverflow-checks" => {
+            "passes `-Coverflow-checks` to rustc, keeping integer overflow panics in --release builds".to_string()
+        }
+        "RUSTFLAGS" => format!("passes `{value}` to rustc for every crate that is built"),
+        _ => format!("sets the environment variable `{name}` for the command"),
+    }
+}
+
+fn program_meaning(program: &str) -> String {
+    match program.rsplit('/').next().unwrap_or(program) {
+        "cargo" => "cargo, which builds and runs the script".to_string(),
+        "rust-script" => {
+            "rust-script (https://rust-script.org), which reads the manifest from the `//! ```cargo` block".to_string()
+        }
+        _ => "the program that runs the script".to_string(),
+    }
+}
+
+fn flag_meaning(flag: &str) -> String {
+    match flag {
+        "run" => "builds the script if it changed, then runs it".to_string(),
+        "-q" | "--quiet" => {
+            "hides cargo's own output (`Compiling ...`), so only the script prints".to_string()
+        }
+        "-Zscript" => {
+            "enables cargo's unstable script support (RFC 3424), reading the manifest from the `---cargo` frontmatter".to_string()
+        }
+        "--release" => {
+            "builds with optimizations: a slower first run, a faster script".to_string()
+        }
+        "--manifest-path" => {
+            "takes the next argument, the script path, as the package to build".to_string()
+        }
+        "+nightly" => {
+            "uses the nightly toolchain (through rustup), where `-Zscript` needs no RUSTC_BOOTSTRAP".to_string()
+        }
+        flag if flag.starts_with('+') => {
+            format!("uses the `{}` toolchain (through rustup)", &flag[1..])
+        }
+        flag if flag.starts_with("-Z") => {
+            format!("enables the unstable cargo feature `{}`", &flag[2..])
+        }
+        _ => "passed to cargo as is".to_string(),
+    }
+}
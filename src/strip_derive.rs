@@ -0,0 +1,264 @@
+//! `--strip-derive`: removes named traits from `#[derive(...)]` lists, so an extract doesn't
+//! depend on the crates providing those derive macros. Imports of a stripped trait that
+//! nothing else mentions are removed too, so they don't keep the crate in use either.
+
+use std::collections::{BTreeMap, HashSet};
+
+use proc_macro2::{TokenStream, TokenTree};
+use syn::punctuated::Punctuated;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{
+    Attribute, Item, ItemEnum, ItemStruct, ItemUnion, Token, UseTree, Visibility, parse_quote,
+};
+
+/// How many derives each `--strip-derive` trait removed, and the imports dropped with them
+pub struct Stripped {
+    pub derives: BTreeMap<String, usize>,
+    pub imports: Vec<String>,
+}
+
+/// Removes every derive of `traits` from `file`. A trait matches by name (`Serialize`),
+/// or by the exact path when it is given with one (`serde::Serialize`).
+pub fn strip_derives(file: &mut syn::File, traits: &[String]) -> Stripped {
+    let mut stripper = DeriveStripper {
+        traits,
+        derives: traits.iter().map(|name| (name.clone(), 0)).collect(),
+        names: HashSet::new(),
+    };
+    stripper.visit_file_mut(file);
+
+    let mut mentions = Mentions::default();
+    mentions.visit_file(file);
+    let unused: HashSet<String> = stripper
+        .names
+        .into_iter()
+        .filter(|name| !mentions.0.contains(name))
+        .collect();
+
+    let mut imports = Vec::new();
+    if !unused.is_empty() {
+        prune_uses(&mut file.items, &unused, &mut imports);
+    }
+
+    Stripped {
+        derives: stripper.derives,
+        imports,
+    }
+}
+
+struct DeriveStripper<'a> {
+    traits: &'a [String],
+    derives: BTreeMap<String, usize>,
+    /// Last path segments of the derives that were removed
+    names: HashSet<String>,
+}
+
+impl DeriveStripper<'_> {
+    fn strip(&mut self, attrs: &mut Vec<Attribute>) {
+        attrs.retain_mut(|attr| {
+            if !attr.path().is_ident("derive") {
+                return true;
+            }
+            let Ok(paths) =
+                attr.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+            else {
+                return true;
+            };
+
+            let count = paths.len();
+            let kept: Vec<&syn::Path> = paths
+                .iter()
+                .filter(|path| match self.matching_trait(path) {
+                    Some(name) => {
+                        *self.derives.entry(name).or_default() += 1;
+                        if let Some(last) = path.segments.last() {
+                            self.names.insert(last.ident.to_string());
+                        }
+                        false
+                    }
+                    None => true,
+                })
+                .collect();
+
+            if kept.len() < count && !kept.is_empty() {
+                *attr = parse_quote!(#[derive(#(#kept),*)]);
+            }
+            !kept.is_empty()
+        });
+    }
+
+    fn matching_trait(&self, path: &syn::Path) -> Option<String> {
+        let written = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("::");
+        let last = path.segments.last()?.ident.to_string();
+
+        self.traits
+            .iter()
+            .find(|name| {
+                let name = name.trim_start_matches("::");
+                if name.contains("::") {
+                    *name == written
+                } else {
+                    *name == last
+                }
+            })
+            .cloned()
+    }
+}
+
+impl VisitMut for DeriveStripper<'_> {
+    fn visit_item_struct_mut(&mut self, item: &mut ItemStruct) {
+        self.strip(&mut item.attrs);
+        syn::visit_mut::visit_item_struct_mut(self, item);
+    }
+
+    fn visit_item_enum_mut(&mut self, item: &mut ItemEnum) {
+        self.strip(&mut item.attrs);
+        syn::visit_mut::visit_item_enum_mut(self, item);
+    }
+
+    fn visit_item_union_mut(&mut self, item: &mut ItemUnion) {
+        self.strip(&mut item.attrs);
+        syn::visit_mut::visit_item_union_mut(self, item);
+    }
+}
+
+/// Identifiers mentioned outside `use` items, including inside macro invocations and
+/// attribute arguments (e.g. a `derive` within `cfg_attr`)
+#[derive(Default)]
+struct Mentions(HashSet<String>);
+
+impl Mentions {
+    fn tokens(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    self.0.insert(ident.to_string());
+                }
+                TokenTree::Group(group) => self.tokens(group.stream()),
+                TokenTree::Punct(_) | TokenTree::Literal(_) => {}
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Mentions {
+    fn visit_ident(&mut self, ident: &'ast proc_macro2::Ident) {
+        self.0.insert(ident.to_string());
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        self.tokens(mac.tokens.clone());
+        syn::visit::visit_macro(self, mac);
+    }
+
+    fn visit_meta_list(&mut self, list: &'ast syn::MetaList) {
+        self.tokens(list.tokens.clone());
+        syn::visit::visit_meta_list(self, list);
+    }
+
+    fn visit_item_use(&mut self, _: &'ast syn::ItemUse) {}
+}
+
+/// Removes the `unused` names from the private `use` items in `items` and nested modules,
+/// dropping `use` items that end up importing nothing
+fn prune_uses(items: &mut Vec<Item>, unused: &HashSet<String>, removed: &mut Vec<String>) {
+    items.retain_mut(|item| match item {
+        // Re-exports are part of the API, so they stay
+        Item::Use(item) if matches!(item.vis, Visibility::Inherited) => {
+            prune_tree(&mut item.tree, unused, &mut Vec::new(), removed)
+        }
+        Item::Mod(module) => {
+            if let Some((_, items)) = &mut module.content {
+                prune_uses(items, unused, removed);
+            }
+            true
+        }
+        _ => true,
+    });
+}
+
+/// Whether anything is left of `tree` once the `unused` names are taken out of it
+fn prune_tree(
+    tree: &mut UseTree,
+    unused: &HashSet<String>,
+    prefix: &mut Vec<String>,
+    removed: &mut Vec<String>,
+) -> bool {
+    match tree {
+        UseTree::Name(name) if unused.contains(&name.ident.to_string()) => {
+            prefix.push(name.ident.to_string());
+            removed.push(prefix.join("::"));
+            prefix.pop();
+            false
+        }
+        UseTree::Path(path) => {
+            prefix.push(path.ident.to_string());
+            let kept = prune_tree(&mut path.tree, unused, prefix, removed);
+            prefix.pop();
+            kept
+        }
+        UseTree::Group(group) => {
+            let before = group.items.len();
+            group.items = std::mem::take(&mut group.items)
+                .into_iter()
+                .filter_map(|mut tree| {
+                    prune_tree(&mut tree, unused, prefix, removed).then_some(tree)
+                })
+                .collect();
+            match group.items.len() {
+                0 => false,
+                1 if before > 1 => {
+                    *tree = group.items.pop().expect("checked above").into_value();
+                    true
+                }
+                _ => true,
+            }
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(code: &str, traits: &[&str]) -> (String, Stripped) {
+        let mut file = syn::parse_file(code).unwrap();
+        let traits: Vec<String> = traits.iter().map(|name| name.to_string()).collect();
+        let stripped = strip_derives(&mut file, &traits);
+        (prettyplease::unparse(&file), stripped)
+    }
+
+    #[test]
+    fn removes_the_trait_and_its_unused_import() {
+        let (code, stripped) = strip(
+            "use serde::Serialize;\n\
+             #[derive(Debug, Serialize)]\n\
+             struct Point { x: i32 }\n",
+            &["Serialize"],
+        );
+        assert_eq!(code, "#[derive(Debug)]\nstruct Point {\n    x: i32,\n}\n");
+        assert_eq!(stripped.derives["Serialize"], 1);
+        assert_eq!(stripped.imports, ["serde::Serialize"]);
+    }
+
+    #[test]
+    fn drops_derives_left_empty_and_keeps_imports_still_used() {
+        let (code, stripped) = strip(
+            "use serde::{Serialize, Serializer};\n\
+             #[derive(serde::Serialize)]\n\
+             enum Kind { A }\n\
+             fn write<S: Serializer>(_: S) {}\n",
+            &["serde::Serialize"],
+        );
+        assert!(!code.contains("derive"), "{code}");
+        assert!(code.starts_with("use serde::Serializer;\n"), "{code}");
+        assert_eq!(stripped.derives["serde::Serialize"], 1);
+    }
+}
@@ -0,0 +1,224 @@
+//! `--strip-private`: drops top-level items without `pub`/`pub(crate)` visibility so an
+//! extract shows only the public surface. Private items that a retained item mentions by
+//! name are kept, since removing them would break the code; the check compares names
+//! only, so it keeps too much rather than too little. A top-level `fn main` is the
+//! script's entry point and always stays, along with what it uses.
+
+use std::collections::HashSet;
+
+use proc_macro2::{TokenStream, TokenTree};
+use syn::visit::Visit;
+use syn::{Item, Visibility};
+
+/// The outcome of stripping: names of the removed items, and of private items that were
+/// kept along with the retained item that uses them
+pub struct Stripped {
+    pub removed: Vec<String>,
+    pub kept: Vec<(String, String)>,
+}
+
+/// Removes the private top-level items of `file` that no retained item refers to
+pub fn strip_private(file: &mut syn::File) -> Stripped {
+    let names: Vec<Option<String>> = file.items.iter().map(private_name).collect();
+    let mut retained: Vec<bool> = names.iter().map(Option::is_none).collect();
+    let mut kept = Vec::new();
+
+    // Impls go with their type, so they're only retained once it is
+    let impl_types: Vec<Option<String>> = file.items.iter().map(impl_type).collect();
+    for (index, ty) in impl_types.iter().enumerate() {
+        if ty
+            .as_ref()
+            .is_some_and(|ty| names.contains(&Some(ty.clone())))
+        {
+            retained[index] = false;
+        }
+    }
+
+    let mut pending: Vec<usize> = (0..file.items.len()).filter(|&i| retained[i]).collect();
+    while let Some(index) = pending.pop() {
+        let mentions = mentions(&file.items[index]);
+        for (other, name) in names.iter().enumerate() {
+            let Some(name) = name else { continue };
+            if retained[other] || !mentions.contains(name) {
+                continue;
+            }
+            retained[other] = true;
+            pending.push(other);
+            kept.push((name.clone(), item_label(&file.items[index])));
+
+            for (impl_index, ty) in impl_types.iter().enumerate() {
+                if ty.as_ref() == Some(name) && !retained[impl_index] {
+                    retained[impl_index] = true;
+                    pending.push(impl_index);
+                }
+            }
+        }
+    }
+
+    let mut removed = Vec::new();
+    let mut retained = retained.into_iter();
+    file.items.retain(|item| {
+        let keep = retained.next().expect("one flag per item");
+        if !keep && let Some(name) = private_name(item) {
+            removed.push(name);
+        }
+        keep
+    });
+
+    Stripped { removed, kept }
+}
+
+/// The name of a private item that can be stripped; `fn main`, `use`s, impls, macros and
+/// extern blocks are never stripped on their own
+fn private_name(item: &Item) -> Option<String> {
+    let vis = match item {
+        Item::Fn(item) if item.sig.ident == "main" => return None,
+        Item::Const(item) => &item.vis,
+        Item::Enum(item) => &item.vis,
+        Item::Fn(item) => &item.vis,
+        Item::Mod(item) => &item.vis,
+        Item::Static(item) => &item.vis,
+        Item::Struct(item) => &item.vis,
+        Item::Trait(item) => &item.vis,
+        Item::TraitAlias(item) => &item.vis,
+        Item::Type(item) => &item.vis,
+        Item::Union(item) => &item.vis,
+        _ => return None,
+    };
+    item_name(item).filter(|_| is_private(vis))
+}
+
+fn item_name(item: &Item) -> Option<String> {
+    let ident = match item {
+        Item::Const(item) => &item.ident,
+        Item::Enum(item) => &item.ident,
+        Item::Fn(item) => &item.sig.ident,
+        Item::Mod(item) => &item.ident,
+        Item::Static(item) => &item.ident,
+        Item::Struct(item) => &item.ident,
+        Item::Trait(item) => &item.ident,
+        Item::TraitAlias(item) => &item.ident,
+        Item::Type(item) => &item.ident,
+        Item::Union(item) => &item.ident,
+        _ => return None,
+    };
+    Some(ident.to_string())
+}
+
+fn is_private(vis: &Visibility) -> bool {
+    match vis {
+        Visibility::Public(_) => false,
+        Visibility::Restricted(restricted) => restricted.path.is_ident("self"),
+        Visibility::Inherited => true,
+    }
+}
+
+/// The last segment of an impl's self type, e.g. `Parser` for `impl<T> Parser<T>`
+pub fn impl_type(item: &Item) -> Option<String> {
+    let Item::Impl(item) = item else { return None };
+    let syn::Type::Path(ty) = &*item.self_ty else {
+        return None;
+    };
+    ty.path
+        .segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+}
+
+fn item_label(item: &Item) -> String {
+    if let Item::Macro(syn::ItemMacro {
+        ident: Some(ident), ..
+    }) = item
+    {
+        return format!("macro `{ident}!`");
+    }
+
+    match (impl_type(item), item_name(item)) {
+        (Some(ty), _) => format!("an impl of `{ty}`"),
+        (None, Some(name)) => format!("`{name}`"),
+        (None, None) => "another item".to_string(),
+    }
+}
+
+/// Every identifier an item mentions, including those inside macro invocations
+pub fn mentions(item: &Item) -> HashSet<String> {
+    let mut collector = Mentions::default();
+    collector.visit_item(item);
+    collector.idents
+}
+
+#[derive(Default)]
+struct Mentions {
+    idents: HashSet<String>,
+}
+
+impl Mentions {
+    fn tokens(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    self.idents.insert(ident.to_string());
+                }
+                TokenTree::Group(group) => self.tokens(group.stream()),
+                TokenTree::Punct(_) | TokenTree::Literal(_) => {}
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Mentions {
+    fn visit_ident(&mut self, ident: &'ast proc_macro2::Ident) {
+        self.idents.insert(ident.to_string());
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        self.tokens(mac.tokens.clone());
+        syn::visit::visit_macro(self, mac);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(code: &str) -> (Vec<String>, Stripped) {
+        let mut file = syn::parse_file(code).unwrap();
+        let stripped = strip_private(&mut file);
+        let names = file.items.iter().filter_map(item_name).collect();
+        (names, stripped)
+    }
+
+    #[test]
+    fn keeps_private_helpers_of_public_items() {
+        let (names, stripped) = strip(
+            "pub fn parse() -> u8 { helper() }\n\
+             fn helper() -> u8 { 1 }\n\
+             fn unused() {}\n\
+             struct Scratch;\n\
+             impl Scratch { fn new() -> Self { Scratch } }\n",
+        );
+        assert_eq!(names, ["parse", "helper"]);
+        assert_eq!(stripped.removed, ["unused", "Scratch"]);
+        assert_eq!(
+            stripped.kept,
+            [("helper".to_string(), "`parse`".to_string())]
+        );
+    }
+
+    #[test]
+    fn keeps_main_and_what_it_uses() {
+        let (names, stripped) = strip("fn main() { run() }\nfn run() {}\nfn unused() {}\n");
+        assert_eq!(names, ["main", "run"]);
+        assert_eq!(stripped.removed, ["unused"]);
+    }
+
+    #[test]
+    fn keeps_private_types_of_crate_visible_items() {
+        let (names, _) = strip(
+            "pub(crate) struct Parser { state: State }\n\
+             struct State;\n\
+             impl State { fn reset(&mut self) {} }\n",
+        );
+        assert_eq!(names, ["Parser", "State"]);
+    }
+}
@@ -0,0 +1,56 @@
+//! Environment placeholders in user-supplied templates (`--module-banner`, `--metadata`
+//! values): `{{env.NAME}}` is replaced with the variable's value, and
+//! `{{env.NAME:-fallback}}` with `fallback` when the variable is unset or empty.
+
+const OPEN: &str = "{{env.";
+const CLOSE: &str = "}}";
+
+/// Replaces every environment placeholder in `text`, failing on a variable that is unset
+/// and has no fallback. Other `{{...}}` text is left as it is.
+pub fn expand_env(text: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        let Some(end) = after.find(CLOSE) else {
+            break;
+        };
+        let (name, fallback) = match after[..end].split_once(":-") {
+            Some((name, fallback)) => (name, Some(fallback)),
+            None => (&after[..end], None),
+        };
+        if !is_variable_name(name) {
+            return Err(format!(
+                "invalid environment variable name '{name}' in '{OPEN}{}{CLOSE}'",
+                &after[..end]
+            ));
+        }
+
+        let value = std::env::var(name).ok().filter(|value| !value.is_empty());
+        let value = match (value, fallback) {
+            (Some(value), _) => value,
+            (None, Some(fallback)) => fallback.to_string(),
+            (None, None) => {
+                return Err(format!(
+                    "environment variable {name} is not set (use {OPEN}{name}:-default{CLOSE} for a fallback)"
+                ));
+            }
+        };
+
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &after[end + CLOSE.len()..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
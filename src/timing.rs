@@ -0,0 +1,68 @@
+//! `--timing`: wall time per pipeline phase, summed over the whole run (batch mode
+//! processes several inputs) and printed as a table on stderr at exit.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TOTALS: Mutex<[Duration; PHASES.len()]> = Mutex::new([Duration::ZERO; PHASES.len()]);
+
+#[derive(Clone, Copy)]
+pub enum Phase {
+    /// Locating the entry point and manifest, reading stdin or fetching a URL
+    Input,
+    /// Reading and parsing module files
+    Inline,
+    Format,
+    /// AST passes before formatting and text passes after it, up to the finished script
+    Transforms,
+    Highlight,
+    Write,
+}
+
+/// Table labels, in the order of [`Phase`]'s variants
+const PHASES: [&str; 6] = [
+    "input resolution",
+    "inlining",
+    "formatting",
+    "transforms",
+    "highlighting",
+    "write",
+];
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Runs `f`, adding its wall time to `phase` when timing is enabled
+pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    TOTALS.lock().unwrap_or_else(|e| e.into_inner())[phase as usize] += elapsed;
+    result
+}
+
+/// Prints the table of phase durations, if timing is enabled
+pub fn report() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let totals = *TOTALS.lock().unwrap_or_else(|e| e.into_inner());
+    let width = PHASES.iter().map(|name| name.len()).max().unwrap_or(0);
+    for (name, elapsed) in PHASES.iter().zip(totals) {
+        eprintln!("{name:<width$}  {}", format_duration(elapsed));
+    }
+    let total: Duration = totals.iter().sum();
+    eprintln!("{:<width$}  {}", "total", format_duration(total));
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:>9.2}ms", duration.as_secs_f64() * 1000.0)
+}
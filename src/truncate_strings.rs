@@ -0,0 +1,77 @@
+//! `--truncate-strings`: shortens long string literals in output shown on a terminal, so
+//! a preview of code embedding large blobs (e.g. base64 data) stays readable. Literals are
+//! found by tokenizing the code, so text that only looks like a string (in comments, or
+//! in the manifest) is left alone.
+
+use std::str::FromStr;
+
+use proc_macro2::{TokenStream, TokenTree};
+
+const MARKER: &str = "...[truncated]...";
+
+/// Replaces the contents of every string literal in `script` longer than `limit` characters
+/// with a marker, keeping the literal's prefix and quotes. Returns `script` unchanged if
+/// its code doesn't tokenize as Rust.
+pub fn truncate_strings(script: &str, limit: usize) -> String {
+    // Only the code after a shebang and frontmatter is tokenized
+    let code_start = match crate::split_frontmatter(script) {
+        Some((header, manifest, _)) => {
+            header.len() + manifest.len() + crate::FRONTMATTER_CLOSE.len()
+        }
+        None if script.starts_with("#!") && !script.starts_with("#![") => {
+            script.find('\n').map_or(script.len(), |end| end + 1)
+        }
+        None => 0,
+    };
+    let Ok(tokens) = TokenStream::from_str(&script[code_start..]) else {
+        return script.to_string();
+    };
+
+    let mut ranges = Vec::new();
+    long_literals(tokens, limit, &mut ranges);
+
+    let mut truncated = String::with_capacity(script.len());
+    let mut copied = 0;
+    for (start, end) in ranges {
+        truncated.push_str(&script[copied..code_start + start]);
+        truncated.push_str(MARKER);
+        copied = code_start + end;
+    }
+    truncated.push_str(&script[copied..]);
+    truncated
+}
+
+/// Byte ranges, in source order, of the contents of the string literals in `tokens` that
+/// are longer than `limit` characters
+fn long_literals(tokens: TokenStream, limit: usize, ranges: &mut Vec<(usize, usize)>) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => long_literals(group.stream(), limit, ranges),
+            TokenTree::Literal(literal) => {
+                let text = literal.to_string();
+                if let Some((open, close)) = string_contents(&text)
+                    && text[open..close].chars().count() > limit
+                {
+                    let start = literal.span().byte_range().start;
+                    ranges.push((start + open, start + close));
+                }
+            }
+            TokenTree::Ident(_) | TokenTree::Punct(_) => {}
+        }
+    }
+}
+
+/// Where the contents of a (byte, C or raw) string literal start and end in its text, or
+/// `None` for other literals
+fn string_contents(literal: &str) -> Option<(usize, usize)> {
+    let prefix = literal.find('"')?;
+    if !literal[..prefix]
+        .chars()
+        .all(|c| matches!(c, 'b' | 'c' | 'r' | '#'))
+    {
+        return None;
+    }
+    // The last quote, so a suffix (`"..."suffix`) isn't part of the contents
+    let close = literal.rfind('"')?;
+    (close > prefix).then_some((prefix + 1, close))
+}
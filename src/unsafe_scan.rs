@@ -0,0 +1,94 @@
+//! `--warn-unsafe` / `--deny-unsafe`: finds `unsafe` code in the inlined crate so shared
+//! scripts can be vetted without reading every module.
+
+use std::fmt;
+
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{ExprUnsafe, ItemForeignMod, ItemImpl, ItemMod, ItemTrait, Signature};
+
+/// One use of `unsafe`, located by module path and line/column in its original file
+pub struct UnsafeSite {
+    kind: String,
+    module: String,
+    line: usize,
+    column: usize,
+}
+
+impl fmt::Display for UnsafeSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in `{}` at {}:{}",
+            self.kind, self.module, self.line, self.column
+        )
+    }
+}
+
+/// Every `unsafe` block, fn, impl, trait and extern block in `file`, in source order
+pub fn find_unsafe(file: &syn::File) -> Vec<UnsafeSite> {
+    let mut finder = UnsafeFinder {
+        module_path: vec!["crate".to_string()],
+        sites: Vec::new(),
+    };
+    finder.visit_file(file);
+    finder.sites
+}
+
+struct UnsafeFinder {
+    module_path: Vec<String>,
+    sites: Vec<UnsafeSite>,
+}
+
+impl UnsafeFinder {
+    fn record(&mut self, kind: String, node: &impl Spanned) {
+        let start = node.span().start();
+        self.sites.push(UnsafeSite {
+            kind,
+            module: self.module_path.join("::"),
+            line: start.line,
+            column: start.column + 1,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for UnsafeFinder {
+    fn visit_item_mod(&mut self, item: &'ast ItemMod) {
+        self.module_path.push(item.ident.to_string());
+        syn::visit::visit_item_mod(self, item);
+        self.module_path.pop();
+    }
+
+    fn visit_expr_unsafe(&mut self, expr: &'ast ExprUnsafe) {
+        self.record("unsafe block".to_string(), &expr.unsafe_token);
+        syn::visit::visit_expr_unsafe(self, expr);
+    }
+
+    fn visit_signature(&mut self, sig: &'ast Signature) {
+        if let Some(token) = &sig.unsafety {
+            self.record(format!("unsafe fn `{}`", sig.ident), token);
+        }
+        syn::visit::visit_signature(self, sig);
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast ItemImpl) {
+        if let Some(token) = &item.unsafety {
+            self.record("unsafe impl".to_string(), token);
+        }
+        syn::visit::visit_item_impl(self, item);
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast ItemTrait) {
+        if let Some(token) = &item.unsafety {
+            self.record(format!("unsafe trait `{}`", item.ident), token);
+        }
+        syn::visit::visit_item_trait(self, item);
+    }
+
+    fn visit_item_foreign_mod(&mut self, item: &'ast ItemForeignMod) {
+        if let Some(token) = &item.unsafety {
+            self.record("unsafe extern block".to_string(), token);
+        }
+        syn::visit::visit_item_foreign_mod(self, item);
+    }
+}
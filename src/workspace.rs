@@ -0,0 +1,214 @@
+//! `--path-deps`: scriptify inlines the `mod`s of one crate and never the code of other
+//! crates, so sibling crates of a workspace have to stay dependencies. This rewrites the
+//! manifest so those dependencies still resolve from a script outside the workspace:
+//! sibling crates the code uses are added as `path` dependencies, relative paths are
+//! made absolute and `workspace = true` entries are replaced by the workspace's
+//! definition.
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{Result, ScriptifyError, glob};
+
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+struct Workspace {
+    root: PathBuf,
+    /// `[workspace.dependencies]` of the root manifest
+    dependencies: toml::Table,
+    /// Package name and directory of each member
+    members: Vec<(String, PathBuf)>,
+}
+
+/// Rewrites the dependencies of `manifest`, whose relative paths are relative to
+/// `manifest_dir`, for use outside its workspace. `used` are the external crates the
+/// code refers to. Returns a description of each change.
+pub fn link_path_dependencies(
+    manifest: &mut toml::Table,
+    manifest_dir: &Path,
+    used: &BTreeSet<String>,
+) -> Result<Vec<String>> {
+    let manifest_dir = std::path::absolute(manifest_dir).map_err(ScriptifyError::io(format!(
+        "failed to resolve path '{}'",
+        manifest_dir.display()
+    )))?;
+    let workspace = find_workspace(&manifest_dir)?;
+    let mut changes = Vec::new();
+
+    for kind in DEPENDENCY_TABLES {
+        let Some(deps) = manifest.get_mut(kind).and_then(toml::Value::as_table_mut) else {
+            continue;
+        };
+        for (name, dep) in deps.iter_mut() {
+            if let Some(change) = localize(name, dep, &manifest_dir, workspace.as_ref())? {
+                changes.push(format!("[{kind}] `{name}`: {change}"));
+            }
+        }
+    }
+
+    let Some(workspace) = workspace else {
+        return Ok(changes);
+    };
+    let own_name = manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(toml::Value::as_str)
+        .map(crate_name);
+    let deps = manifest
+        .entry("dependencies")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| {
+            ScriptifyError::ManifestError("`dependencies` is not a table".to_string())
+        })?;
+
+    for (name, dir) in &workspace.members {
+        let declared = deps.keys().any(|key| crate_name(key) == crate_name(name));
+        if declared
+            || own_name.as_deref() == Some(&crate_name(name))
+            || !used.contains(&crate_name(name))
+        {
+            continue;
+        }
+        let path = path_value(dir);
+        changes.push(format!(
+            "[dependencies] `{name}`: added as a path dependency ({path})"
+        ));
+        let dep = toml::Table::from_iter([("path".to_string(), path)]);
+        deps.insert(name.clone(), toml::Value::Table(dep));
+    }
+
+    Ok(changes)
+}
+
+/// Makes one dependency entry independent of the workspace, describing what changed
+fn localize(
+    name: &str,
+    dep: &mut toml::Value,
+    manifest_dir: &Path,
+    workspace: Option<&Workspace>,
+) -> Result<Option<String>> {
+    let Some(table) = dep.as_table_mut() else {
+        return Ok(None);
+    };
+
+    if table.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+        let Some(workspace) = workspace else {
+            return Err(ScriptifyError::ManifestError(format!(
+                "`{name}` is inherited with `workspace = true`, but no workspace manifest was found"
+            )));
+        };
+        let inherited = match workspace.dependencies.get(name) {
+            Some(toml::Value::String(version)) => {
+                toml::Table::from_iter([("version".to_string(), version.clone().into())])
+            }
+            Some(toml::Value::Table(inherited)) => inherited.clone(),
+            _ => {
+                return Err(ScriptifyError::ManifestError(format!(
+                    "`{name}` is inherited with `workspace = true`, but the workspace doesn't define it"
+                )));
+            }
+        };
+
+        table.remove("workspace");
+        for (key, value) in inherited {
+            match (key.as_str(), table.get_mut(&key), value) {
+                ("features", Some(toml::Value::Array(own)), toml::Value::Array(extra)) => {
+                    own.extend(extra);
+                }
+                ("path", _, toml::Value::String(path)) => {
+                    table.insert(key, path_value(&workspace.root.join(path)));
+                }
+                (_, None, value) => {
+                    table.insert(key, value);
+                }
+                _ => {}
+            }
+        }
+        return Ok(Some("resolved from the workspace".to_string()));
+    }
+
+    match table.get("path").and_then(toml::Value::as_str) {
+        Some(path) if Path::new(path).is_relative() => {
+            let absolute = path_value(&manifest_dir.join(path));
+            let change = format!("path made absolute ({absolute})");
+            table.insert("path".to_string(), absolute);
+            Ok(Some(change))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The workspace whose root manifest is in `dir` or one of its ancestors
+fn find_workspace(dir: &Path) -> Result<Option<Workspace>> {
+    for root in dir.ancestors() {
+        let manifest_path = root.join("Cargo.toml");
+        let Ok(content) = std::fs::read_to_string(&manifest_path) else {
+            continue;
+        };
+        let manifest: toml::Table = toml::from_str(&content)?;
+        let Some(workspace) = manifest.get("workspace").and_then(toml::Value::as_table) else {
+            continue;
+        };
+
+        let dependencies = workspace
+            .get("dependencies")
+            .and_then(toml::Value::as_table)
+            .cloned()
+            .unwrap_or_default();
+        let patterns = workspace
+            .get("members")
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(toml::Value::as_str);
+
+        let mut members = Vec::new();
+        for pattern in patterns {
+            let pattern = format!("{}/{pattern}/Cargo.toml", root.display());
+            for member_manifest in glob::expand(&pattern, false)? {
+                let content = std::fs::read_to_string(&member_manifest).map_err(
+                    ScriptifyError::io(format!("failed to read '{}'", member_manifest.display())),
+                )?;
+                let member: toml::Table = toml::from_str(&content)?;
+                if let Some(name) = member
+                    .get("package")
+                    .and_then(|package| package.get("name"))
+                    .and_then(toml::Value::as_str)
+                {
+                    let dir = member_manifest.parent().unwrap_or(root).to_path_buf();
+                    members.push((name.to_string(), dir));
+                }
+            }
+        }
+
+        return Ok(Some(Workspace {
+            root: root.to_path_buf(),
+            dependencies,
+            members,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// A package name as written in code
+fn crate_name(package: &str) -> String {
+    package.replace('-', "_")
+}
+
+/// `path` as a TOML string, with `.` and `..` components folded away without touching the
+/// file system
+pub fn path_value(path: &Path) -> toml::Value {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    toml::Value::String(normalized.display().to_string())
+}
@@ -0,0 +1,83 @@
+//! `--emit-wrapper`: wraps a generated script in a POSIX shell script that writes it to a
+//! cache file and runs it, for systems where the shebang tricks don't work (no `env -S`,
+//! or a filesystem that doesn't allow running the `.rs` file itself).
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+const DELIMITER: &str = "SCRIPTIFY_RUST_SOURCE";
+
+/// Shell script embedding `script` as a quoted heredoc and running it with the command of
+/// `shebang`. The source is written once per content hash, so cargo's build cache keeps
+/// working across runs.
+pub fn shell_wrapper(script: &str, shebang: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    script.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let delimiter = heredoc_delimiter(script);
+    let mut source = script.to_string();
+    if !source.ends_with('\n') {
+        source.push('\n');
+    }
+
+    format!(
+        r#"#!/bin/sh
+# Runs the Rust script below, generated by scriptify, from a cached copy
+set -e
+dir="${{XDG_CACHE_HOME:-$HOME/.cache}}/scriptify"
+script="$dir/script-{hash:016x}.rs"
+if [ ! -f "$script" ]; then
+    mkdir -p "$dir"
+    cat > "$script.$$" <<'{delimiter}'
+{source}{delimiter}
+    mv "$script.$$" "$script"
+fi
+exec {command} "$script" "$@"
+"#,
+        command = command(shebang)
+    )
+}
+
+/// A delimiter that no line of `script` equals, so the heredoc can't end early
+fn heredoc_delimiter(script: &str) -> String {
+    let taken = |candidate: &str| script.lines().any(|line| line == candidate);
+    (0..)
+        .map(|n| match n {
+            0 => DELIMITER.to_string(),
+            n => format!("{DELIMITER}_{n}"),
+        })
+        .find(|candidate| !taken(candidate))
+        .expect("some suffix is always free")
+}
+
+/// The shebang's command as shell words. `env` stays to apply variable assignments, but
+/// without `-S`: the shell already splits the words.
+fn command(shebang: &str) -> String {
+    let mut words = shebang
+        .strip_prefix("#!")
+        .unwrap_or(shebang)
+        .split_whitespace()
+        .peekable();
+    let mut command = Vec::new();
+    if words
+        .next_if(|word| word.rsplit('/').next() == Some("env"))
+        .is_some()
+    {
+        words.next_if_eq(&"-S");
+        command.push("env".to_string());
+    }
+    command.extend(words.map(shell_word));
+    command.join(" ")
+}
+
+/// Single-quotes `word` unless it only has characters the shell takes literally
+fn shell_word(word: &str) -> String {
+    let literal = word
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_=+./:,@%".contains(c));
+    if literal {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
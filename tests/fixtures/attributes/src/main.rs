@@ -0,0 +1,10 @@
+mod model;
+
+use model::Event;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let event = Event::new("started");
+    println!("{}", serde_json::to_string(&event)?);
+    Ok(())
+}
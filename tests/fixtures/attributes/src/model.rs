@@ -0,0 +1,22 @@
+#![allow(dead_code)]
+
+#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[cfg(feature = "timestamps")]
+    pub at: u64,
+}
+
+impl Event {
+    #[must_use]
+    pub fn new(kind: &str) -> Self {
+        Event {
+            kind: kind.to_string(),
+            #[cfg(feature = "timestamps")]
+            at: 0,
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod routes;
+
+fn main() {
+    println!("serving {}", routes::ROOT);
+}
@@ -0,0 +1 @@
+pub const ROOT: &str = "/";
@@ -0,0 +1,8 @@
+// Networking, inlined from net.rs
+mod net;
+
+// Entry point
+fn main() {
+    let conn = net::Conn::open("localhost");
+    println!("{}", conn.describe());
+}
@@ -0,0 +1,23 @@
+//! Connections
+
+// Frame layout, inlined from net/wire.rs
+pub mod wire;
+
+// Plain TCP for now
+/// An open connection
+pub struct Conn {
+    host: String,
+}
+
+impl Conn {
+    // Doesn't connect until first use
+    pub fn open(host: &str) -> Self {
+        Conn {
+            host: host.to_string(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} (wire v{})", self.host, wire::VERSION)
+    }
+}
@@ -0,0 +1,3 @@
+// Bumped on every breaking change
+// to the frame layout
+pub const VERSION: u8 = 2;
@@ -0,0 +1,42 @@
+//! Edition 2024 syntax the formatter has to print back unchanged
+
+use std::fmt::Display;
+
+unsafe extern "C" {
+    pub safe fn abs(x: i32) -> i32;
+    pub safe static environ: *const *const u8;
+    pub fn labs(x: i64) -> i64;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn exported() -> u8 {
+    7
+}
+
+fn labels<'a>(items: &'a [u8]) -> impl Iterator<Item = String> + use<'a> {
+    items.iter().map(|item| item.to_string())
+}
+
+fn describe(value: Option<&dyn Display>) -> String {
+    if let Some(value) = value
+        && let text = value.to_string()
+        && !text.is_empty()
+    {
+        text
+    } else {
+        String::from("nothing")
+    }
+}
+
+fn main() {
+    let number = 5u8;
+    let pointer = &raw const number;
+    let r#gen = unsafe { *pointer };
+    let double = async |x: u8| x * 2;
+    let _ = double(number);
+    let magnitude = abs(-3) as i64 + unsafe { labs(-4) };
+    println!("{} {} {magnitude}", describe(Some(&r#gen)), exported());
+    for label in labels(&[1, 2]) {
+        println!("{label}");
+    }
+}
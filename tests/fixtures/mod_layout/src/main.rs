@@ -0,0 +1,10 @@
+// `modern` lives in modern.rs, `legacy` in legacy/mod.rs, and `both` has both files,
+// which rustc rejects as ambiguous
+mod both;
+mod legacy;
+mod modern;
+
+fn main() {
+    modern::run();
+    legacy::run();
+}
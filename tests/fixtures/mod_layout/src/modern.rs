@@ -0,0 +1,5 @@
+mod nested;
+
+pub fn run() {
+    nested::run();
+}
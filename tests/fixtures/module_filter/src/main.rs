@@ -0,0 +1,8 @@
+mod api;
+mod db;
+
+fn main() {
+    api::users::list();
+    api::v1::deep::ping();
+    db::connect();
+}
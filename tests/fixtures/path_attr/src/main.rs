@@ -0,0 +1,5 @@
+mod net;
+
+fn main() {
+    net::send();
+}
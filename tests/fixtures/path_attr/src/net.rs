@@ -0,0 +1,14 @@
+// A top-level `#[path]` in a non-mod-rs file resolves next to this file (src/wire.rs),
+// one inside an inline module below `net/` (src/net/inner/deep.rs)
+#[path = "wire.rs"]
+mod wire;
+
+mod inner {
+    #[path = "deep.rs"]
+    pub mod deep;
+}
+
+pub fn send() {
+    wire::encode();
+    inner::deep::dive();
+}
@@ -0,0 +1,7 @@
+// Loaded through `#[path]`, so this file owns its directory like a mod.rs: `codec` is
+// src/codec.rs, not src/wire/codec.rs
+mod codec;
+
+pub fn encode() {
+    codec::run();
+}